@@ -0,0 +1,334 @@
+use bevy::prelude::*;
+use spectre_core::Movement;
+use std::f32::consts::PI;
+use tracing::warn;
+
+use crate::AnimationState;
+
+/// The number of directions a directional animation distinguishes between
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DirectionMode {
+    Four,
+    Eight,
+}
+
+/// A facing direction, in screen space (i.e. Up is +y)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+impl Direction {
+    /// Quantizes a vector into the closest direction for the given mode.
+    /// Returns None for (near) zero length vectors.
+    pub fn from_vector(vector: Vec2, mode: DirectionMode) -> Option<Direction> {
+        if vector.length_squared() < 0.0001 {
+            return None;
+        }
+
+        let angle = vector.y().atan2(vector.x());
+
+        match mode {
+            DirectionMode::Four => {
+                let sector = (angle / (PI / 2.)).round() as i32;
+                Some(match sector.rem_euclid(4) {
+                    0 => Direction::Right,
+                    1 => Direction::Up,
+                    2 => Direction::Left,
+                    _ => Direction::Down,
+                })
+            }
+            DirectionMode::Eight => {
+                let sector = (angle / (PI / 4.)).round() as i32;
+                Some(match sector.rem_euclid(8) {
+                    0 => Direction::Right,
+                    1 => Direction::UpRight,
+                    2 => Direction::Up,
+                    3 => Direction::UpLeft,
+                    4 => Direction::Left,
+                    5 => Direction::DownLeft,
+                    6 => Direction::Down,
+                    _ => Direction::DownRight,
+                })
+            }
+        }
+    }
+
+    /// returns the direction reflected about the vertical axis
+    pub fn mirrored(self) -> Direction {
+        match self {
+            Direction::UpRight => Direction::UpLeft,
+            Direction::Right => Direction::Left,
+            Direction::DownRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::DownRight,
+            Direction::Left => Direction::Right,
+            Direction::UpLeft => Direction::UpRight,
+            other => other,
+        }
+    }
+}
+
+/// The clip indices for each direction of a single logical animation (e.g. walk)
+#[derive(Clone, Debug, Default)]
+pub struct DirectionalClips(pub Vec<(Direction, usize)>);
+
+impl DirectionalClips {
+    /// returns the clip for the direction, if one is defined
+    pub fn get(&self, direction: Direction) -> Option<usize> {
+        self.0
+            .iter()
+            .find(|(clip_direction, _)| *clip_direction == direction)
+            .map(|(_, clip)| *clip)
+    }
+}
+
+/// A component which picks the clip variant in `AnimationState` from the entity's
+/// facing. The facing follows `Movement::velocity` while the entity is moving.
+pub struct DirectionalAnimation {
+    pub mode: DirectionMode,
+
+    /// If true, directions without a clip use the mirrored direction's clip
+    /// with the sprite flipped horizontally (e.g. left plays right, flipped)
+    pub mirror_horizontal: bool,
+
+    /// The directional variants of each logical animation. When an entity also has
+    /// an `AnimationStateMachine`, the state clip indices refer to this list.
+    pub groups: Vec<DirectionalClips>,
+
+    /// The index of the logical animation in `groups` that is currently playing
+    pub current_group: usize,
+
+    /// The current facing, which is kept when the entity stops moving
+    pub facing: Direction,
+
+    /// True when the current clip is a mirrored variant
+    pub is_flipped: bool,
+
+    needs_apply: bool,
+}
+
+impl DirectionalAnimation {
+    pub fn new(mode: DirectionMode, groups: Vec<DirectionalClips>) -> Self {
+        DirectionalAnimation {
+            mode,
+            mirror_horizontal: false,
+            groups,
+            current_group: 0,
+            facing: Direction::Down,
+            is_flipped: false,
+            needs_apply: true,
+        }
+    }
+
+    /// Mirrors directions without their own clip, flipping the sprite horizontally
+    pub fn with_mirroring(mut self) -> Self {
+        self.mirror_horizontal = true;
+        self
+    }
+
+    /// Sets the logical animation being played, restarting it if it changed.
+    /// Returns false if there is no such group.
+    pub fn set_group(&mut self, group: usize) -> bool {
+        if group >= self.groups.len() {
+            warn!(
+                group,
+                count = self.groups.len(),
+                "Unknown directional animation group"
+            );
+            return false;
+        }
+
+        self.current_group = group;
+        self.needs_apply = true;
+        true
+    }
+
+    /// Sets the facing from a vector, ignoring (near) zero vectors. Returns true if it changed.
+    pub fn face(&mut self, vector: Vec2) -> bool {
+        match Direction::from_vector(vector, self.mode) {
+            Some(direction) if direction != self.facing => {
+                self.facing = direction;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// returns the clip and whether it should be flipped for the current group and facing
+    pub fn resolve(&self) -> Option<(usize, bool)> {
+        let clips = self.groups.get(self.current_group)?;
+
+        if let Some(clip) = clips.get(self.facing) {
+            return Some((clip, false));
+        }
+
+        if self.mirror_horizontal {
+            if let Some(clip) = clips.get(self.facing.mirrored()) {
+                return Some((clip, true));
+            }
+        }
+
+        None
+    }
+
+    /// Faces the velocity and plays the clip for the current group and facing. A new group
+    /// starts from its first frame, while turning keeps the current frame so walk cycles
+    /// don't stutter. Returns true if a clip was applied.
+    pub fn update(&mut self, velocity: Vec2, animation: &mut AnimationState) -> bool {
+        let turned = self.face(velocity);
+        if !turned && !self.needs_apply {
+            return false;
+        }
+
+        let (clip, flipped) = match self.resolve() {
+            Some(resolved) => resolved,
+            None => return false,
+        };
+
+        match animation
+            .animations
+            .get(animation.current_animation)
+            .cloned()
+        {
+            Some((first, last)) if !self.needs_apply => {
+                // turning keeps the current frame so walk cycles don't stutter
+                if clip != animation.current_animation {
+                    let offset = animation.current_idx.max(first).min(last) - first;
+                    let elapsed = animation.frame_elapsed;
+                    let cycles = animation.completed_cycles;
+                    let is_finished = animation.is_finished;
+                    animation.set_animation(clip);
+                    animation.seek(offset);
+                    animation.frame_elapsed = elapsed;
+                    animation.completed_cycles = cycles;
+                    animation.is_finished = is_finished;
+                }
+            }
+            // a new logical animation, or one which isn't playing a valid clip,
+            // starts from its first frame
+            _ => {
+                if !animation.set_animation(clip) {
+                    return false;
+                }
+            }
+        }
+
+        self.needs_apply = false;
+        self.is_flipped = flipped;
+        true
+    }
+}
+
+pub(crate) fn update_directional_animations(
+    mut query: Query<(
+        &Movement,
+        &mut DirectionalAnimation,
+        &mut AnimationState,
+        &mut TextureAtlasSprite,
+        &mut Transform,
+    )>,
+) {
+    for (movement, mut directional, mut animation, mut sprite, mut transform) in &mut query.iter() {
+        let was_flipped = directional.is_flipped;
+        if !directional.update(movement.velocity, &mut animation) {
+            continue;
+        }
+
+        sprite.index = animation.get_frame_index();
+
+        if directional.is_flipped != was_flipped {
+            let mut scale = transform.scale();
+            scale.set_x(-scale.x());
+            transform.set_non_uniform_scale(scale);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantizes_vectors_into_directions() {
+        let four = |x, y| Direction::from_vector(Vec2::new(x, y), DirectionMode::Four);
+        let eight = |x, y| Direction::from_vector(Vec2::new(x, y), DirectionMode::Eight);
+
+        assert_eq!(four(1., 0.), Some(Direction::Right));
+        assert_eq!(four(0., 1.), Some(Direction::Up));
+        assert_eq!(four(-1., 0.1), Some(Direction::Left));
+        assert_eq!(four(-1., -0.1), Some(Direction::Left));
+        assert_eq!(four(0.3, -1.), Some(Direction::Down));
+        assert_eq!(four(1., 1.1), Some(Direction::Up));
+
+        assert_eq!(eight(1., 1.), Some(Direction::UpRight));
+        assert_eq!(eight(-1., 1.), Some(Direction::UpLeft));
+        assert_eq!(eight(-1., -1.), Some(Direction::DownLeft));
+        assert_eq!(eight(1., -1.), Some(Direction::DownRight));
+        assert_eq!(eight(1., 0.3), Some(Direction::Right));
+        assert_eq!(eight(0.3, -1.), Some(Direction::Down));
+    }
+
+    #[test]
+    fn ignores_zero_vectors() {
+        let mut directional = DirectionalAnimation::new(DirectionMode::Eight, Vec::new());
+
+        assert_eq!(
+            Direction::from_vector(Vec2::zero(), DirectionMode::Four),
+            None
+        );
+        assert!(!directional.face(Vec2::new(0.001, 0.)));
+        assert_eq!(directional.facing, Direction::Down);
+    }
+
+    #[test]
+    fn mirrors_missing_directions() {
+        let mut directional = DirectionalAnimation::new(
+            DirectionMode::Four,
+            vec![DirectionalClips(vec![
+                (Direction::Right, 0),
+                (Direction::Up, 1),
+            ])],
+        )
+        .with_mirroring();
+
+        directional.face(Vec2::new(-1., 0.));
+        assert_eq!(directional.resolve(), Some((0, true)));
+
+        directional.face(Vec2::new(0., -1.));
+        assert_eq!(directional.resolve(), None);
+
+        assert!(!directional.set_group(1));
+        assert_eq!(directional.current_group, 0);
+    }
+
+    #[test]
+    fn recovers_from_invalid_animation_states() {
+        let mut directional = DirectionalAnimation::new(
+            DirectionMode::Four,
+            vec![DirectionalClips(vec![(Direction::Right, 1)])],
+        );
+        directional.needs_apply = false;
+
+        // turning with an out of range animation restarts the clip instead of panicking
+        let mut animation = AnimationState {
+            animations: vec![(0, 1), (2, 3)],
+            current_animation: 7,
+            ..Default::default()
+        };
+        assert!(directional.update(Vec2::new(1., 0.), &mut animation));
+        assert_eq!(animation.current_animation, 1);
+        assert_eq!(animation.get_frame_index(), 2);
+
+        // states without animations are skipped
+        let mut empty = AnimationState::default();
+        directional.facing = Direction::Down;
+        assert!(!directional.update(Vec2::new(1., 0.), &mut empty));
+    }
+}
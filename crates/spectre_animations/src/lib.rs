@@ -1,4 +1,14 @@
 use bevy::prelude::*;
+use spectre_time::{ClockBinding, GameClocks};
+use tracing::warn;
+
+mod directional;
+mod state_machine;
+mod tween;
+
+pub use directional::*;
+pub use state_machine::*;
+pub use tween::*;
 
 pub mod prelude {
     pub use crate::*;
@@ -8,17 +18,81 @@ pub struct AnimationPlugin;
 
 impl Plugin for AnimationPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system(animate_sprites.system());
+        app.add_event::<AnimationFrameTriggered>()
+            .add_system(animate_sprites.system())
+            .add_system(sync_animation_velocity.system())
+            .add_system(update_animation_state_machines.system())
+            // directional clips are picked after the state machines, so a state change
+            // and a turn in the same frame both show straight away
+            .add_stage_after("update", "directional_animations")
+            .add_system_to_stage(
+                "directional_animations",
+                update_directional_animations.system(),
+            );
     }
 }
 
 pub type AnimationFrameRange = (usize, usize);
 
+/// Selects which clock advances an animation
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnimationClock {
+    /// Advances on wall clock time, keeps playing while the game is paused (e.g. menus)
+    RealTime,
+
+    /// Advances on the world clock in `GameClocks`, so pauses and speeds up with the game.
+    /// Entities with a `ClockBinding` advance on their bound clock instead.
+    GameTime,
+
+    /// Advances on the named clock in `GameClocks`, e.g. a player's bullet time clock
+    Named(String),
+}
+
+impl AnimationClock {
+    pub fn named(name: &str) -> Self {
+        AnimationClock::Named(String::from(name))
+    }
+
+    /// returns the time in seconds this clock advanced by in the current frame,
+    /// for an entity with the given clock binding
+    pub fn delta(&self, time: &Time, clocks: &GameClocks, binding: Option<&ClockBinding>) -> f32 {
+        match (self, binding) {
+            (AnimationClock::RealTime, _) => time.delta_seconds,
+            (AnimationClock::GameTime, _) => clocks.bound(binding).map_or(0., |clock| clock.delta),
+            (AnimationClock::Named(name), _) => clocks.delta(name),
+        }
+    }
+}
+
 pub struct AnimationState {
     pub animations: Vec<AnimationFrameRange>,
     pub current_animation: usize,
     pub current_idx: usize,
     pub is_playing: bool,
+
+    /// If true the animation plays from the last frame towards the first
+    pub is_reversed: bool,
+
+    /// A multiplier applied to the clock delta, e.g. tied to attack speed
+    pub playback_speed: f32,
+
+    /// The time in seconds each frame is shown for at a playback speed of 1.0
+    pub frame_duration: f32,
+
+    /// The time accumulated towards the next frame
+    pub frame_elapsed: f32,
+
+    /// The clock which drives this animation
+    pub clock: AnimationClock,
+
+    /// If false the animation stops on its last frame instead of wrapping around
+    pub is_looping: bool,
+
+    /// Set when a non-looping animation reaches its last frame
+    pub is_finished: bool,
+
+    /// The number of times the current animation has wrapped back to its first frame
+    pub completed_cycles: usize,
 }
 
 impl Default for AnimationState {
@@ -28,6 +102,14 @@ impl Default for AnimationState {
             current_animation: 0,
             current_idx: 0,
             is_playing: false,
+            is_reversed: false,
+            playback_speed: 1.0,
+            frame_duration: 0.1,
+            frame_elapsed: 0.0,
+            clock: AnimationClock::RealTime,
+            is_looping: true,
+            is_finished: false,
+            completed_cycles: 0,
         }
     }
 }
@@ -36,64 +118,334 @@ impl AnimationState {
     // sets the animation on an animation state and moves to the first frame
     pub fn set_animation(&mut self, animation_idx: usize) -> bool {
         if animation_idx >= self.animations.len() {
-            println!(
-                "Unknown animation {}, max idx {}",
-                animation_idx,
-                self.animations.len()
+            warn!(
+                animation = animation_idx,
+                count = self.animations.len(),
+                "Unknown animation"
             );
             return false;
         }
 
         self.current_animation = animation_idx;
-        self.current_idx = self.animations[animation_idx].0;
+        self.current_idx = if self.is_reversed {
+            self.animations[animation_idx].1
+        } else {
+            self.animations[animation_idx].0
+        };
+        self.frame_elapsed = 0.0;
+        self.is_finished = false;
+        self.completed_cycles = 0;
         true
     }
 
-    // increments the animation frame
+    // increments the animation frame, in reverse if the animation is reversed
     pub fn incr(&mut self) {
-        if self.current_idx == self.animations[self.current_animation].1 {
-            self.current_idx = self.animations[self.current_animation].0;
+        let (first, last) = match self.animations.get(self.current_animation) {
+            Some(range) => *range,
+            None => return,
+        };
+
+        if self.is_reversed {
+            if self.current_idx <= first {
+                self.current_idx = last;
+                self.completed_cycles += 1;
+            } else {
+                self.current_idx -= 1;
+            }
+        } else if self.current_idx >= last {
+            self.current_idx = first;
+            self.completed_cycles += 1;
         } else {
             self.current_idx += 1;
         }
     }
 
-    pub fn get_frame_index(&self) -> u32 {
-        if !self.is_playing {
-            return 0;
+    /// returns true if the current frame is the final frame in the playback direction
+    pub fn is_on_last_frame(&self) -> bool {
+        let (first, last) = match self.animations.get(self.current_animation) {
+            Some(range) => *range,
+            None => return true,
+        };
+        if self.is_reversed {
+            self.current_idx <= first
+        } else {
+            self.current_idx >= last
+        }
+    }
+
+    /// returns true once a non-looping animation has finished,
+    /// or a looping animation has played through at least once
+    pub fn has_completed_cycle(&self) -> bool {
+        self.is_finished || self.completed_cycles > 0
+    }
+
+    /// Advances the animation by the given clock delta, returns the frame indices it moved
+    /// to in order, which is empty if the frame didn't change. A long delta can pass
+    /// through several frames.
+    pub fn tick(&mut self, delta: f32) -> Vec<usize> {
+        let mut frames = Vec::new();
+        if !self.is_playing
+            || self.is_finished
+            || self.frame_duration <= 0.
+            || self.animations.is_empty()
+        {
+            return frames;
         }
 
-        return (self.animations[self.current_animation].0 + self.current_idx) as u32;
+        self.frame_elapsed += delta * self.playback_speed;
+
+        while self.frame_elapsed >= self.frame_duration {
+            self.frame_elapsed -= self.frame_duration;
+
+            if !self.is_looping && self.is_on_last_frame() {
+                self.is_finished = true;
+                self.frame_elapsed = 0.0;
+                break;
+            }
+
+            self.incr();
+            frames.push(self.current_idx);
+        }
+
+        frames
+    }
+
+    /// Stops advancing frames, keeping the current frame visible
+    pub fn pause(&mut self) {
+        self.is_playing = false;
+    }
+
+    /// Continues advancing frames from the current frame
+    pub fn resume(&mut self) {
+        self.is_playing = true;
+    }
+
+    /// Moves to the given frame, relative to the start of the current animation.
+    /// Frames past the end of the animation are clamped to the last frame.
+    pub fn seek(&mut self, frame: usize) {
+        let (first, last) = match self.animations.get(self.current_animation) {
+            Some(range) => *range,
+            None => return,
+        };
+        self.current_idx = (first + frame).min(last);
+        self.frame_elapsed = 0.0;
+    }
+
+    /// Toggles the playback direction of the animation
+    pub fn reverse(&mut self) {
+        self.is_reversed = !self.is_reversed;
+    }
+
+    /// Sets the playback speed multiplier, negative values are treated as 0
+    pub fn set_playback_speed(&mut self, playback_speed: f32) {
+        self.playback_speed = playback_speed.max(0.);
+    }
+
+    pub fn get_frame_index(&self) -> u32 {
+        self.current_idx as u32
+    }
+}
+
+/// A named trigger which fires when an animation reaches a frame, e.g. to play a footstep sound
+pub struct FrameTrigger {
+    pub animation: usize,
+
+    /// The frame, relative to the start of the animation
+    pub frame: usize,
+    pub name: String,
+}
+
+/// A component which sends `AnimationFrameTriggered` events when the animation reaches given frames
+#[derive(Default)]
+pub struct AnimationFrameTriggers(pub Vec<FrameTrigger>);
+
+impl AnimationFrameTriggers {
+    /// Adds a trigger which fires when the animation reaches the frame
+    pub fn with_trigger(mut self, animation: usize, frame: usize, name: &str) -> Self {
+        self.0.push(FrameTrigger {
+            animation,
+            frame,
+            name: String::from(name),
+        });
+        self
     }
 }
 
-fn animate_sprites(mut query: Query<(&mut Timer, &mut TextureAtlasSprite, &mut AnimationState)>) {
-    for (timer, mut sprite, mut state) in &mut query.iter() {
-        if timer.finished {
-            state.incr();
-            sprite.index = state.get_frame_index();
+/// An event sent when an entity's animation reaches a frame in its `AnimationFrameTriggers`
+pub struct AnimationFrameTriggered {
+    pub entity: Entity,
+    pub name: String,
+}
+
+fn animate_sprites(
+    time: Res<Time>,
+    clocks: Res<GameClocks>,
+    mut triggered: ResMut<Events<AnimationFrameTriggered>>,
+    mut query: Query<(
+        Entity,
+        &mut TextureAtlasSprite,
+        &mut AnimationState,
+        Option<&AnimationFrameTriggers>,
+        Option<&ClockBinding>,
+    )>,
+) {
+    for (entity, mut sprite, mut state, triggers, binding) in &mut query.iter() {
+        let delta = state.clock.delta(&time, &clocks, binding);
+        let frames = state.tick(delta);
+        if frames.is_empty() {
+            continue;
+        }
+
+        sprite.index = state.get_frame_index();
+
+        let triggers = match triggers {
+            Some(triggers) => triggers,
+            None => continue,
+        };
+        let first = match state.animations.get(state.current_animation) {
+            Some((first, _)) => *first,
+            None => continue,
+        };
+
+        // frames skipped over by a long tick still fire their triggers
+        for frame in frames.iter().filter_map(|idx| idx.checked_sub(first)) {
+            for trigger in triggers.0.iter() {
+                if trigger.animation == state.current_animation && trigger.frame == frame {
+                    triggered.send(AnimationFrameTriggered {
+                        entity,
+                        name: trigger.name.clone(),
+                    });
+                }
+            }
         }
     }
 }
 
-pub fn spawn_animated_spritesheet(
-    mut commands: Commands,
+/// spawns a playing sprite sheet, returns the commands so more components can be added to it
+pub fn spawn_animated_spritesheet<'a>(
+    commands: &'a mut Commands,
     texture_atlas_handle: Handle<TextureAtlas>,
     frame_duration: f32,
     animation_frames: Vec<(usize, usize)>,
     location: Vec3,
-) {
+    clock: AnimationClock,
+) -> &'a mut Commands {
+    let mut state = AnimationState {
+        animations: animation_frames,
+        is_playing: true,
+        frame_duration,
+        clock,
+        ..Default::default()
+    };
+    state.set_animation(1);
+
     commands
         .spawn(SpriteSheetComponents {
             texture_atlas: texture_atlas_handle,
+            sprite: TextureAtlasSprite::new(state.get_frame_index()),
             transform: Transform::from_scale(1.0).with_translation(location),
             ..Default::default()
         })
-        .with(Timer::from_seconds(frame_duration, true))
-        .with(AnimationState {
-            animations: animation_frames,
-            current_animation: 1,
-            current_idx: 0,
+        .with(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectre_time::{Clock, WORLD_CLOCK};
+
+    fn playing(animations: Vec<AnimationFrameRange>) -> AnimationState {
+        AnimationState {
+            animations,
             is_playing: true,
-        });
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ignores_states_without_animations() {
+        let mut state = playing(Vec::new());
+
+        state.incr();
+        state.seek(3);
+        assert!(state.tick(1.).is_empty());
+        assert!(state.is_on_last_frame());
+        assert_eq!(state.get_frame_index(), 0);
+    }
+
+    #[test]
+    fn ticks_frames_and_wraps() {
+        let mut state = playing(vec![(2, 4)]);
+        state.set_animation(0);
+
+        assert!(state.tick(0.05).is_empty());
+        assert_eq!(state.tick(0.05), vec![3]);
+        assert_eq!(state.get_frame_index(), 3);
+
+        // several frames can pass in a single tick, and each is reported
+        assert_eq!(state.tick(0.2), vec![4, 2]);
+        assert_eq!(state.get_frame_index(), 2);
+        assert_eq!(state.completed_cycles, 1);
+    }
+
+    #[test]
+    fn finishes_non_looping_animations() {
+        let mut state = AnimationState {
+            is_looping: false,
+            ..playing(vec![(0, 1)])
+        };
+
+        state.tick(0.1);
+        state.tick(0.1);
+        assert!(state.is_finished);
+        assert!(state.has_completed_cycle());
+        assert_eq!(state.get_frame_index(), 1);
+        assert!(state.tick(0.1).is_empty());
+    }
+
+    #[test]
+    fn plays_reversed_animations() {
+        let mut state = playing(vec![(0, 2)]);
+        state.reverse();
+        state.set_animation(0);
+        assert_eq!(state.get_frame_index(), 2);
+
+        state.tick(0.1);
+        assert_eq!(state.get_frame_index(), 1);
+
+        state.seek(0);
+        state.tick(0.1);
+        assert_eq!(state.get_frame_index(), 2);
+        assert_eq!(state.completed_cycles, 1);
+    }
+
+    #[test]
+    fn scales_ticks_by_playback_speed() {
+        let mut state = playing(vec![(0, 3)]);
+
+        state.set_playback_speed(2.);
+        state.tick(0.1);
+        assert_eq!(state.get_frame_index(), 2);
+
+        state.set_playback_speed(-1.);
+        assert_eq!(state.playback_speed, 0.);
+        assert!(state.tick(1.).is_empty());
+    }
+
+    #[test]
+    fn follows_clock_bindings() {
+        let mut clocks =
+            GameClocks::default().with_clock("player_1", Clock::new(0.5).with_parent(WORLD_CLOCK));
+        clocks.advance(1., 1.);
+        let time = Time::default();
+        let binding = ClockBinding::new("player_1");
+
+        let game_clock = AnimationClock::GameTime;
+        assert_eq!(game_clock.delta(&time, &clocks, None), 1.);
+        assert_eq!(game_clock.delta(&time, &clocks, Some(&binding)), 0.5);
+
+        // named clocks ignore the binding
+        let world_clock = AnimationClock::named(WORLD_CLOCK);
+        assert_eq!(world_clock.delta(&time, &clocks, Some(&binding)), 1.);
+    }
 }
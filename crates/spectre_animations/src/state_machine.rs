@@ -0,0 +1,484 @@
+use bevy::prelude::*;
+use spectre_core::Movement;
+use std::collections::HashMap;
+use tracing::warn;
+
+use crate::{AnimationState, DirectionalAnimation};
+
+/// The animation states a character can be in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CharacterAnimation {
+    Idle,
+    Walk,
+    Attack,
+    Cast,
+    Die,
+}
+
+/// Component data which drives animation transitions. Game systems should
+/// update this on the entity, and the state machine picks the animation from it.
+#[derive(Default)]
+pub struct AnimationInputs {
+    /// For entities with a `Movement` component this is copied from `Movement::velocity`
+    /// each frame, so movement is the single source of velocity for animations
+    pub velocity: Vec2,
+    pub is_attacking: bool,
+    pub is_casting: bool,
+    pub is_dead: bool,
+}
+
+impl AnimationInputs {
+    /// returns true if the entity is moving faster than a small threshold
+    pub fn is_moving(&self) -> bool {
+        self.velocity.length_squared() > 0.01
+    }
+}
+
+/// Describes how an animation state plays its clip
+#[derive(Clone, Copy, Debug)]
+pub struct AnimationStateConfig {
+    /// The index of the clip in `AnimationState::animations`, or the index
+    /// of the group in `DirectionalAnimation::groups` for directional entities
+    pub clip: usize,
+
+    /// Transitions into a state with a higher priority than the current
+    /// state interrupt it, even if it is waiting for its clip to finish
+    pub priority: u8,
+
+    /// If false the clip stops on its last frame
+    pub is_looping: bool,
+
+    /// If true the state can only be left (by equal or lower priority
+    /// transitions) once its clip has played through
+    pub finish_before_exit: bool,
+
+    /// If true this is a one-shot overlay, which returns to the
+    /// state it interrupted once its clip has played through
+    pub is_overlay: bool,
+
+    /// If true overlays can't be played over this state with `play_overlay`, e.g. die
+    pub is_terminal: bool,
+}
+
+impl AnimationStateConfig {
+    /// A looping state which can be left at any time, e.g. idle or walk
+    pub fn looping(clip: usize, priority: u8) -> Self {
+        AnimationStateConfig {
+            clip,
+            priority,
+            is_looping: true,
+            finish_before_exit: false,
+            is_overlay: false,
+            is_terminal: false,
+        }
+    }
+
+    /// A one-shot clip which plays through and then returns to the previous state, e.g. attack
+    pub fn overlay(clip: usize, priority: u8) -> Self {
+        AnimationStateConfig {
+            clip,
+            priority,
+            is_looping: false,
+            finish_before_exit: true,
+            is_overlay: true,
+            is_terminal: false,
+        }
+    }
+
+    /// A one-shot clip which holds its last frame, e.g. die
+    pub fn terminal(clip: usize, priority: u8) -> Self {
+        AnimationStateConfig {
+            clip,
+            priority,
+            is_looping: false,
+            finish_before_exit: true,
+            is_overlay: false,
+            is_terminal: true,
+        }
+    }
+}
+
+/// A transition into the `to` state when `condition` is true.
+/// If `from` is None the transition applies from any state.
+pub struct AnimationTransition {
+    pub from: Option<CharacterAnimation>,
+    pub to: CharacterAnimation,
+    pub condition: fn(&AnimationInputs) -> bool,
+}
+
+/// A component which selects the animation for an entity from its `AnimationInputs`
+pub struct AnimationStateMachine {
+    pub current: CharacterAnimation,
+    states: HashMap<CharacterAnimation, AnimationStateConfig>,
+    transitions: Vec<AnimationTransition>,
+
+    /// The state to return to when the current overlay finishes
+    return_to: Option<CharacterAnimation>,
+
+    /// Set when the state has changed but the clip has not yet been applied
+    needs_apply: bool,
+}
+
+impl AnimationStateMachine {
+    pub fn new(initial: CharacterAnimation) -> Self {
+        AnimationStateMachine {
+            current: initial,
+            states: HashMap::default(),
+            transitions: Vec::default(),
+            return_to: None,
+            needs_apply: true,
+        }
+    }
+
+    /// Creates a state machine with the default character transitions.
+    /// Death overrides everything, casting and attacking are overlays
+    /// which return to idle or walk, and idle/walk follow the velocity.
+    pub fn character(idle: usize, walk: usize, attack: usize, cast: usize, die: usize) -> Self {
+        AnimationStateMachine::new(CharacterAnimation::Idle)
+            .with_state(
+                CharacterAnimation::Idle,
+                AnimationStateConfig::looping(idle, 0),
+            )
+            .with_state(
+                CharacterAnimation::Walk,
+                AnimationStateConfig::looping(walk, 0),
+            )
+            .with_state(
+                CharacterAnimation::Attack,
+                AnimationStateConfig::overlay(attack, 10),
+            )
+            .with_state(
+                CharacterAnimation::Cast,
+                AnimationStateConfig::overlay(cast, 10),
+            )
+            .with_state(
+                CharacterAnimation::Die,
+                AnimationStateConfig::terminal(die, 100),
+            )
+            .with_transition(None, CharacterAnimation::Die, |inputs| inputs.is_dead)
+            .with_transition(None, CharacterAnimation::Cast, |inputs| {
+                inputs.is_casting && !inputs.is_dead
+            })
+            .with_transition(None, CharacterAnimation::Attack, |inputs| {
+                inputs.is_attacking && !inputs.is_dead
+            })
+            .with_transition(
+                Some(CharacterAnimation::Idle),
+                CharacterAnimation::Walk,
+                |inputs| inputs.is_moving(),
+            )
+            .with_transition(
+                Some(CharacterAnimation::Walk),
+                CharacterAnimation::Idle,
+                |inputs| !inputs.is_moving(),
+            )
+    }
+
+    /// Adds or replaces the configuration for a state
+    pub fn with_state(mut self, state: CharacterAnimation, config: AnimationStateConfig) -> Self {
+        self.states.insert(state, config);
+        self
+    }
+
+    /// Adds a transition, transitions are checked in the order they are added
+    pub fn with_transition(
+        mut self,
+        from: Option<CharacterAnimation>,
+        to: CharacterAnimation,
+        condition: fn(&AnimationInputs) -> bool,
+    ) -> Self {
+        self.transitions.push(AnimationTransition {
+            from,
+            to,
+            condition,
+        });
+        self
+    }
+
+    /// returns the configuration for the given state
+    pub fn get_config(&self, state: CharacterAnimation) -> Option<&AnimationStateConfig> {
+        self.states.get(&state)
+    }
+
+    /// Immediately plays a one-shot overlay, returning to the current state when it finishes.
+    /// The overlay isn't played over terminal states or states with a higher priority,
+    /// returns true if it was played.
+    pub fn play_overlay(&mut self, overlay: CharacterAnimation) -> bool {
+        if overlay == self.current {
+            return false;
+        }
+
+        let priority = match self.states.get(&overlay) {
+            Some(config) => config.priority,
+            None => {
+                warn!(state = ?overlay, "No animation configured for overlay");
+                return false;
+            }
+        };
+
+        let leaving_overlay = match self.states.get(&self.current) {
+            Some(current) if current.is_terminal || current.priority > priority => return false,
+            Some(current) => current.is_overlay,
+            None => false,
+        };
+
+        self.enter(overlay, leaving_overlay);
+        true
+    }
+
+    /// Evaluates the transitions for the given inputs and moves to the next state if required.
+    /// `clip_completed` should be true if the current state's clip has played through.
+    /// Returns true if the state changed.
+    pub fn evaluate(&mut self, inputs: &AnimationInputs, clip_completed: bool) -> bool {
+        let current_config = match self.states.get(&self.current) {
+            Some(config) => *config,
+            None => {
+                warn!(state = ?self.current, "No animation configured for state");
+                return false;
+            }
+        };
+
+        // find the highest priority transition which is triggered
+        let mut candidate: Option<(CharacterAnimation, u8)> = None;
+        for transition in self.transitions.iter() {
+            if transition.to == self.current {
+                continue;
+            }
+
+            if let Some(from) = transition.from {
+                if from != self.current {
+                    continue;
+                }
+            }
+
+            if !(transition.condition)(inputs) {
+                continue;
+            }
+
+            let priority = match self.states.get(&transition.to) {
+                Some(config) => config.priority,
+                None => continue,
+            };
+
+            match candidate {
+                Some((_, best)) if best >= priority => {}
+                _ => candidate = Some((transition.to, priority)),
+            }
+        }
+
+        let is_locked = current_config.finish_before_exit && !clip_completed;
+
+        if let Some((next, priority)) = candidate {
+            if !is_locked || priority > current_config.priority {
+                self.enter(next, current_config.is_overlay);
+                return true;
+            }
+        }
+
+        // overlays return to the state they interrupted when done
+        if current_config.is_overlay && clip_completed {
+            let previous = self.return_to.take().unwrap_or(CharacterAnimation::Idle);
+            self.current = previous;
+            self.needs_apply = true;
+            return true;
+        }
+
+        false
+    }
+
+    fn enter(&mut self, next: CharacterAnimation, leaving_overlay: bool) {
+        let is_overlay = self
+            .states
+            .get(&next)
+            .map(|config| config.is_overlay)
+            .unwrap_or(false);
+
+        if is_overlay {
+            // chained overlays return to the original state
+            if !leaving_overlay {
+                self.return_to = Some(self.current);
+            }
+        } else {
+            self.return_to = None;
+        }
+
+        self.current = next;
+        self.needs_apply = true;
+    }
+
+    /// Applies the clip for the current state to the animation state, if it has changed.
+    /// For directional entities the directional animation picks the clip variant.
+    pub fn apply(
+        &mut self,
+        animation: &mut AnimationState,
+        directional: Option<&mut DirectionalAnimation>,
+    ) -> bool {
+        if !self.needs_apply {
+            return false;
+        }
+
+        let config = match self.states.get(&self.current) {
+            Some(config) => config,
+            None => return false,
+        };
+
+        self.needs_apply = false;
+        animation.is_looping = config.is_looping;
+        animation.is_playing = true;
+
+        // directional clips are restarted by `update_directional_animations`, which runs
+        // in a later stage so it picks the facing after the state has changed
+        match directional {
+            Some(directional) => {
+                if !directional.set_group(config.clip) {
+                    warn!(state = ?self.current, clip = config.clip, "Unable to play state on directional animation");
+                }
+                false
+            }
+            None => animation.set_animation(config.clip),
+        }
+    }
+}
+
+/// Copies the velocity of entities with a `Movement` component into their `AnimationInputs`
+pub(crate) fn sync_animation_velocity(mut query: Query<(&Movement, &mut AnimationInputs)>) {
+    for (movement, mut inputs) in &mut query.iter() {
+        inputs.velocity = movement.velocity;
+    }
+}
+
+pub(crate) fn update_animation_state_machines(
+    mut query: Query<(
+        &AnimationInputs,
+        &mut AnimationStateMachine,
+        &mut AnimationState,
+        &mut TextureAtlasSprite,
+        Option<&mut DirectionalAnimation>,
+    )>,
+) {
+    for (inputs, mut machine, mut animation, mut sprite, mut directional) in &mut query.iter() {
+        machine.evaluate(&inputs, animation.has_completed_cycle());
+
+        if machine.apply(&mut animation, directional.as_deref_mut()) {
+            sprite.index = animation.get_frame_index();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Direction, DirectionMode, DirectionalClips};
+
+    fn inputs() -> AnimationInputs {
+        AnimationInputs::default()
+    }
+
+    #[test]
+    fn picks_the_highest_priority_transition() {
+        let mut machine = AnimationStateMachine::character(0, 1, 2, 3, 4);
+        let attacking_while_dead = AnimationInputs {
+            is_attacking: true,
+            is_dead: true,
+            ..inputs()
+        };
+
+        assert!(machine.evaluate(&attacking_while_dead, false));
+        assert_eq!(machine.current, CharacterAnimation::Die);
+    }
+
+    #[test]
+    fn waits_for_one_shot_clips_to_finish() {
+        let mut machine = AnimationStateMachine::character(0, 1, 2, 3, 4);
+        let attacking = AnimationInputs {
+            is_attacking: true,
+            ..inputs()
+        };
+        let casting = AnimationInputs {
+            is_casting: true,
+            ..inputs()
+        };
+
+        machine.evaluate(&attacking, false);
+        assert_eq!(machine.current, CharacterAnimation::Attack);
+
+        // equal priority transitions wait for the clip
+        assert!(!machine.evaluate(&casting, false));
+        assert_eq!(machine.current, CharacterAnimation::Attack);
+
+        // higher priority transitions don't
+        let dead = AnimationInputs {
+            is_dead: true,
+            ..inputs()
+        };
+        assert!(machine.evaluate(&dead, false));
+        assert_eq!(machine.current, CharacterAnimation::Die);
+    }
+
+    #[test]
+    fn returns_from_overlays() {
+        let mut machine = AnimationStateMachine::character(0, 1, 2, 3, 4);
+        let walking = AnimationInputs {
+            velocity: Vec2::new(1., 0.),
+            ..inputs()
+        };
+
+        machine.evaluate(&walking, false);
+        assert_eq!(machine.current, CharacterAnimation::Walk);
+
+        assert!(machine.play_overlay(CharacterAnimation::Attack));
+        assert!(!machine.evaluate(&walking, false));
+
+        assert!(machine.evaluate(&walking, true));
+        assert_eq!(machine.current, CharacterAnimation::Walk);
+    }
+
+    #[test]
+    fn doesnt_play_overlays_over_terminal_states() {
+        let mut machine = AnimationStateMachine::character(0, 1, 2, 3, 4).with_state(
+            CharacterAnimation::Cast,
+            AnimationStateConfig::overlay(3, 200),
+        );
+        let dead = AnimationInputs {
+            is_dead: true,
+            ..inputs()
+        };
+
+        machine.evaluate(&dead, false);
+        assert!(!machine.play_overlay(CharacterAnimation::Attack));
+        assert!(!machine.play_overlay(CharacterAnimation::Cast));
+        assert_eq!(machine.current, CharacterAnimation::Die);
+    }
+
+    #[test]
+    fn changes_state_and_turns_in_the_same_frame() {
+        let mut machine = AnimationStateMachine::character(0, 1, 2, 3, 4);
+        let mut directional = DirectionalAnimation::new(
+            DirectionMode::Four,
+            vec![
+                DirectionalClips(vec![(Direction::Down, 0), (Direction::Right, 1)]),
+                DirectionalClips(vec![(Direction::Down, 2), (Direction::Right, 3)]),
+            ],
+        );
+        let mut animation = AnimationState {
+            animations: vec![(0, 1), (2, 3), (4, 5), (6, 9)],
+            is_playing: true,
+            ..Default::default()
+        };
+        directional.update(Vec2::zero(), &mut animation);
+        assert_eq!(animation.current_animation, 0);
+
+        // starts walking to the right
+        let walking = AnimationInputs {
+            velocity: Vec2::new(1., 0.),
+            ..inputs()
+        };
+        machine.evaluate(&walking, false);
+        machine.apply(&mut animation, Some(&mut directional));
+        assert!(directional.update(walking.velocity, &mut animation));
+
+        assert_eq!(machine.current, CharacterAnimation::Walk);
+        assert_eq!(animation.current_animation, 3);
+        assert_eq!(animation.get_frame_index(), 6);
+    }
+}
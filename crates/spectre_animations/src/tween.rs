@@ -0,0 +1,664 @@
+use bevy::prelude::*;
+use spectre_time::{ClockBinding, GameClocks};
+use std::{collections::HashMap, f32::consts::PI};
+
+use crate::AnimationClock;
+
+/// Easing functions which map linear progress (0 to 1) onto an eased progress
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    BackOut,
+    BounceOut,
+}
+
+impl Easing {
+    /// applies the easing to a progress value, which is clamped between 0 and 1
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.max(0.).min(1.);
+
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => t * (2. - t),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2. * t * t
+                } else {
+                    -1. + (4. - 2. * t) * t
+                }
+            }
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => {
+                let f = t - 1.;
+                f * f * f + 1.
+            }
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4. * t * t * t
+                } else {
+                    let f = 2. * t - 2.;
+                    0.5 * f * f * f + 1.
+                }
+            }
+            Easing::SineIn => 1. - (t * PI / 2.).cos(),
+            Easing::SineOut => (t * PI / 2.).sin(),
+            Easing::SineInOut => 0.5 * (1. - (t * PI).cos()),
+            Easing::BackOut => {
+                let overshoot = 1.70158;
+                let f = t - 1.;
+                f * f * ((overshoot + 1.) * f + overshoot) + 1.
+            }
+            Easing::BounceOut => {
+                if t < 1. / 2.75 {
+                    7.5625 * t * t
+                } else if t < 2. / 2.75 {
+                    let f = t - 1.5 / 2.75;
+                    7.5625 * f * f + 0.75
+                } else if t < 2.5 / 2.75 {
+                    let f = t - 2.25 / 2.75;
+                    7.5625 * f * f + 0.9375
+                } else {
+                    let f = t - 2.625 / 2.75;
+                    7.5625 * f * f + 0.984375
+                }
+            }
+        }
+    }
+}
+
+/// The value animated by a tween step
+#[derive(Clone, Copy, Debug)]
+pub enum TweenTarget {
+    /// Moves the entity's `Transform`
+    Translation { from: Vec3, to: Vec3 },
+
+    /// Scales the entity's `Transform`
+    Scale { from: Vec3, to: Vec3 },
+
+    /// Rotates the entity's `Transform`
+    Rotation { from: Quat, to: Quat },
+
+    /// Changes the color of the entity's `ColorMaterial`, e.g. to fade it. The entity is given
+    /// its own copy of the material, so other entities using it are unchanged.
+    Color { from: Color, to: Color },
+
+    /// Changes the width of the entity's UI `Style` in pixels, e.g. a health bar
+    Width { from: f32, to: f32 },
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+fn lerp_vec3(from: Vec3, to: Vec3, t: f32) -> Vec3 {
+    from + (to - from) * t
+}
+
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    Color::rgba(
+        lerp(from.r, to.r, t),
+        lerp(from.g, to.g, t),
+        lerp(from.b, to.b, t),
+        lerp(from.a, to.a, t),
+    )
+}
+
+/// A single step in a tween sequence
+#[derive(Clone, Copy, Debug)]
+pub struct TweenStep {
+    pub target: TweenTarget,
+
+    /// The time in seconds taken to move from the start to the end value
+    pub duration: f32,
+
+    /// The time in seconds to hold the start value before the step begins
+    pub delay: f32,
+    pub easing: Easing,
+}
+
+impl TweenStep {
+    pub fn new(target: TweenTarget, duration: f32) -> Self {
+        TweenStep {
+            target,
+            duration,
+            delay: 0.,
+            easing: Easing::Linear,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn with_delay(mut self, delay: f32) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    fn total_duration(&self) -> f32 {
+        // avoid spinning forever on zero length repeating tweens
+        (self.delay + self.duration).max(0.001)
+    }
+}
+
+/// How many times a tween sequence plays
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TweenRepeat {
+    Once,
+    Times(usize),
+    Forever,
+}
+
+/// An event sent when a tween finishes playing
+pub struct TweenCompleted {
+    pub entity: Entity,
+
+    /// The user defined tag of the tween, used to tell tweens apart
+    pub tag: u32,
+}
+
+/// A component which plays a sequence of tween steps on an entity
+pub struct Tween {
+    pub steps: Vec<TweenStep>,
+    pub repeat: TweenRepeat,
+
+    /// If true, each repeat plays the sequence forwards and then backwards
+    pub yoyo: bool,
+
+    /// Use `AnimationClock::RealTime` for menus and `AnimationClock::GameTime` for in-world effects,
+    /// which follow the entity's `ClockBinding` if it has one, or `AnimationClock::Named` to
+    /// follow a clock in `GameClocks`
+    pub clock: AnimationClock,
+    pub tag: u32,
+    pub is_playing: bool,
+    pub is_finished: bool,
+
+    current_step: usize,
+    step_elapsed: f32,
+    completed_loops: usize,
+    is_reversed: bool,
+
+    /// Set by `new` and `restart` so the start value is applied on the next tick
+    is_restarted: bool,
+
+    /// True if the sampled value changed in the last tick, see `is_changed`
+    is_changed: bool,
+}
+
+impl Tween {
+    pub fn new(clock: AnimationClock) -> Self {
+        Tween {
+            steps: Vec::default(),
+            repeat: TweenRepeat::Once,
+            yoyo: false,
+            clock,
+            tag: 0,
+            is_playing: true,
+            is_finished: false,
+            current_step: 0,
+            step_elapsed: 0.,
+            completed_loops: 0,
+            is_reversed: false,
+            is_restarted: true,
+            is_changed: false,
+        }
+    }
+
+    /// Creates a tween with a single step
+    pub fn single(
+        clock: AnimationClock,
+        target: TweenTarget,
+        duration: f32,
+        easing: Easing,
+    ) -> Self {
+        Tween::new(clock).then(TweenStep::new(target, duration).with_easing(easing))
+    }
+
+    /// Appends a step to the sequence
+    pub fn then(mut self, step: TweenStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: TweenRepeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn with_yoyo(mut self) -> Self {
+        self.yoyo = true;
+        self
+    }
+
+    pub fn with_tag(mut self, tag: u32) -> Self {
+        self.tag = tag;
+        self
+    }
+
+    /// Restarts the tween from the beginning
+    pub fn restart(&mut self) {
+        self.current_step = 0;
+        self.step_elapsed = 0.;
+        self.completed_loops = 0;
+        self.is_reversed = false;
+        self.is_finished = false;
+        self.is_playing = true;
+        self.is_restarted = true;
+    }
+
+    /// returns true if the sampled value changed in the last tick. Paused and finished tweens
+    /// don't change, so they stop writing to the entity once their value has been applied.
+    pub fn is_changed(&self) -> bool {
+        self.is_changed
+    }
+
+    /// Advances the tween by the clock delta, returns true if the tween finished during this tick
+    pub fn tick(&mut self, delta: f32) -> bool {
+        // new and restarted tweens apply their start value, even if their clock is stopped
+        self.is_changed = std::mem::replace(&mut self.is_restarted, false);
+        if !self.is_playing || self.is_finished || self.steps.is_empty() {
+            return false;
+        }
+
+        if delta > 0. {
+            self.is_changed = true;
+        }
+        self.step_elapsed += delta;
+
+        while self.step_elapsed >= self.steps[self.current_step].total_duration() {
+            self.step_elapsed -= self.steps[self.current_step].total_duration();
+
+            if !self.advance_step() {
+                self.is_finished = true;
+                self.step_elapsed = self.steps[self.current_step].total_duration();
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Moves to the next step, returns false if the tween has finished
+    fn advance_step(&mut self) -> bool {
+        if !self.is_reversed {
+            if self.current_step + 1 < self.steps.len() {
+                self.current_step += 1;
+                return true;
+            }
+
+            if self.yoyo {
+                // play the last step again, backwards
+                self.is_reversed = true;
+                return true;
+            }
+        } else if self.current_step > 0 {
+            self.current_step -= 1;
+            return true;
+        }
+
+        // a full loop has been played
+        self.completed_loops += 1;
+        let should_repeat = match self.repeat {
+            TweenRepeat::Once => false,
+            TweenRepeat::Times(times) => self.completed_loops < times,
+            TweenRepeat::Forever => true,
+        };
+
+        if should_repeat {
+            self.current_step = 0;
+            self.is_reversed = false;
+        }
+
+        should_repeat
+    }
+
+    /// returns the target of the current step and its eased progress
+    pub fn sample(&self) -> Option<(TweenTarget, f32)> {
+        let step = self.steps.get(self.current_step)?;
+
+        let local = self.step_elapsed - step.delay;
+        let mut t = if local <= 0. {
+            0.
+        } else if step.duration <= 0. {
+            1.
+        } else {
+            (local / step.duration).min(1.)
+        };
+
+        if self.is_reversed {
+            t = 1. - t;
+        }
+
+        Some((step.target, step.easing.apply(t)))
+    }
+}
+
+fn tick_tweens(
+    time: Res<Time>,
+    clocks: Res<GameClocks>,
+    mut completed: ResMut<Events<TweenCompleted>>,
+    mut query: Query<(Entity, &mut Tween, Option<&ClockBinding>)>,
+) {
+    for (entity, mut tween, binding) in &mut query.iter() {
+        let delta = tween.clock.delta(&time, &clocks, binding);
+        if tween.tick(delta) {
+            completed.send(TweenCompleted {
+                entity,
+                tag: tween.tag,
+            });
+        }
+    }
+}
+
+fn apply_transform_tweens(mut query: Query<(&Tween, &mut Transform)>) {
+    for (tween, mut transform) in &mut query.iter() {
+        if !tween.is_changed() {
+            continue;
+        }
+
+        match tween.sample() {
+            Some((TweenTarget::Translation { from, to }, t)) => {
+                transform.set_translation(lerp_vec3(from, to, t));
+            }
+            Some((TweenTarget::Scale { from, to }, t)) => {
+                transform.set_non_uniform_scale(lerp_vec3(from, to, t));
+            }
+            Some((TweenTarget::Rotation { from, to }, t)) => {
+                transform.set_rotation(from.lerp(to, t));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A component for entities whose material has been copied for their color tween,
+/// holding the copy. The copy is removed from the assets when the entity is despawned.
+pub struct TweenedMaterial(pub Handle<ColorMaterial>);
+
+/// Color tweens give each entity its own copy of its material the first time they apply,
+/// so entities which share a material aren't tweened along with it
+fn apply_color_tweens(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<(
+        Entity,
+        &Tween,
+        &mut Handle<ColorMaterial>,
+        Option<&TweenedMaterial>,
+    )>,
+) {
+    for (entity, tween, mut handle, tweened) in &mut query.iter() {
+        if !tween.is_changed() {
+            continue;
+        }
+
+        let color = match tween.sample() {
+            Some((TweenTarget::Color { from, to }, t)) => lerp_color(from, to, t),
+            _ => continue,
+        };
+
+        if tweened.is_none() {
+            let copy = match materials.get(&handle) {
+                Some(material) => ColorMaterial {
+                    color: material.color,
+                    texture: material.texture,
+                },
+                None => continue,
+            };
+
+            *handle = materials.add(copy);
+            commands.insert_one(entity, TweenedMaterial(*handle));
+        }
+
+        if let Some(material) = materials.get_mut(&handle) {
+            material.color = color;
+        }
+    }
+}
+
+/// Removes the material copies of entities which were despawned or lost their `TweenedMaterial`
+fn remove_tweened_materials(
+    mut copies: Local<HashMap<Entity, Handle<ColorMaterial>>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<(Entity, &TweenedMaterial)>,
+) {
+    for (entity, tweened) in &mut query.iter() {
+        copies.insert(entity, tweened.0);
+    }
+
+    for entity in query.removed::<TweenedMaterial>().iter() {
+        if let Some(copy) = copies.remove(entity) {
+            materials.remove(&copy);
+        }
+    }
+}
+
+fn apply_style_tweens(mut query: Query<(&Tween, &mut Style)>) {
+    for (tween, mut style) in &mut query.iter() {
+        if !tween.is_changed() {
+            continue;
+        }
+
+        if let Some((TweenTarget::Width { from, to }, t)) = tween.sample() {
+            style.size.width = Val::Px(lerp(from, to, t));
+        }
+    }
+}
+
+pub struct TweenPlugin;
+
+impl Plugin for TweenPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<TweenCompleted>()
+            .add_stage_after("update", "tweens")
+            .add_system_to_stage("tweens", tick_tweens.system())
+            // tweens are applied in a later stage, so they always show the current tick
+            .add_stage_after("tweens", "apply_tweens")
+            .add_system_to_stage("apply_tweens", apply_transform_tweens.system())
+            .add_system_to_stage("apply_tweens", apply_color_tweens.system())
+            .add_system_to_stage("apply_tweens", apply_style_tweens.system())
+            .add_system_to_stage("apply_tweens", remove_tweened_materials.system());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn width_tween() -> Tween {
+        Tween::single(
+            AnimationClock::RealTime,
+            TweenTarget::Width { from: 0., to: 10. },
+            1.,
+            Easing::Linear,
+        )
+    }
+
+    #[test]
+    fn easing_starts_and_ends_at_bounds() {
+        for easing in [
+            Easing::Linear,
+            Easing::QuadInOut,
+            Easing::CubicOut,
+            Easing::SineInOut,
+            Easing::BackOut,
+            Easing::BounceOut,
+        ]
+        .iter()
+        {
+            assert!(easing.apply(0.).abs() < 0.001, "{:?}", easing);
+            assert!((easing.apply(1.) - 1.).abs() < 0.001, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn finishes_once_and_holds_end_value() {
+        let mut tween = width_tween();
+
+        assert!(!tween.tick(0.5));
+        let (_, t) = tween.sample().unwrap();
+        assert!((t - 0.5).abs() < 0.001);
+
+        assert!(tween.tick(0.6));
+        assert!(tween.is_finished);
+        let (_, t) = tween.sample().unwrap();
+        assert!((t - 1.).abs() < 0.001);
+
+        assert!(!tween.tick(1.));
+    }
+
+    #[test]
+    fn yoyo_plays_backwards() {
+        let mut tween = width_tween().with_yoyo();
+
+        assert!(!tween.tick(1.25));
+        let (_, t) = tween.sample().unwrap();
+        assert!((t - 0.75).abs() < 0.001);
+
+        assert!(tween.tick(1.));
+        let (_, t) = tween.sample().unwrap();
+        assert!(t.abs() < 0.001);
+    }
+
+    #[test]
+    fn repeats_a_number_of_times() {
+        let mut tween = width_tween().with_repeat(TweenRepeat::Times(3));
+
+        assert!(!tween.tick(1.5));
+        assert!(!tween.tick(1.));
+        let (_, t) = tween.sample().unwrap();
+        assert!((t - 0.5).abs() < 0.001);
+
+        assert!(tween.tick(0.5));
+        assert!(tween.is_finished);
+    }
+
+    #[test]
+    fn plays_steps_in_sequence_after_their_delay() {
+        let mut tween = width_tween()
+            .then(TweenStep::new(TweenTarget::Width { from: 10., to: 20. }, 1.).with_delay(0.5));
+
+        tween.tick(1.25);
+        match tween.sample() {
+            Some((TweenTarget::Width { from, .. }, t)) => {
+                // the second step holds its start value during its delay
+                assert_eq!(from, 10.);
+                assert_eq!(t, 0.);
+            }
+            other => panic!("unexpected sample {:?}", other),
+        }
+
+        tween.tick(1.);
+        let (_, t) = tween.sample().unwrap();
+        assert!((t - 0.75).abs() < 0.001);
+
+        assert!(tween.tick(0.25));
+    }
+
+    #[test]
+    fn only_changes_while_playing() {
+        let mut tween = width_tween();
+
+        // the start value is applied on the first tick, even with a stopped clock
+        tween.tick(0.);
+        assert!(tween.is_changed());
+        tween.tick(0.);
+        assert!(!tween.is_changed());
+
+        tween.tick(0.5);
+        assert!(tween.is_changed());
+        tween.is_playing = false;
+        tween.tick(0.5);
+        assert!(!tween.is_changed());
+
+        // the end value is applied on the tick the tween finishes, and not after
+        tween.is_playing = true;
+        tween.tick(1.);
+        assert!(tween.is_finished && tween.is_changed());
+        tween.tick(1.);
+        assert!(!tween.is_changed());
+    }
+
+    fn run_system(system: &mut Box<dyn System>, world: &mut World, resources: &mut Resources) {
+        system.update_archetype_access(world);
+        system.run(world, resources);
+        system.run_thread_local(world, resources);
+    }
+
+    #[test]
+    fn tweens_a_copy_of_shared_materials() {
+        let mut world = World::new();
+        let mut resources = Resources::default();
+        let mut materials = Assets::<ColorMaterial>::default();
+        let shared = materials.add(ColorMaterial::color(Color::WHITE));
+        resources.insert(materials);
+
+        let mut tween = Tween::single(
+            AnimationClock::RealTime,
+            TweenTarget::Color {
+                from: Color::rgba(1., 1., 1., 1.),
+                to: Color::rgba(1., 1., 1., 0.),
+            },
+            1.,
+            Easing::Linear,
+        );
+        tween.tick(0.5);
+        let entity = world.spawn((tween, shared));
+
+        let mut apply = apply_color_tweens.system();
+        apply.initialize(&mut resources);
+        run_system(&mut apply, &mut world, &mut resources);
+
+        let copy = *world.get::<Handle<ColorMaterial>>(entity).unwrap();
+        assert_ne!(copy, shared);
+        assert!(world.get::<TweenedMaterial>(entity).is_ok());
+        {
+            let materials = resources.get::<Assets<ColorMaterial>>().unwrap();
+            assert_eq!(materials.get(&shared).unwrap().color.a, 1.);
+            assert!((materials.get(&copy).unwrap().color.a - 0.5).abs() < 0.001);
+        }
+
+        // the copy is removed once the entity is despawned
+        let mut remove = remove_tweened_materials.system();
+        remove.initialize(&mut resources);
+        run_system(&mut remove, &mut world, &mut resources);
+        world.despawn(entity).unwrap();
+        run_system(&mut remove, &mut world, &mut resources);
+
+        let materials = resources.get::<Assets<ColorMaterial>>().unwrap();
+        assert!(materials.get(&copy).is_none());
+        assert!(materials.get(&shared).is_some());
+    }
+
+    #[test]
+    fn stops_writing_styles_once_finished() {
+        let mut world = World::new();
+        let mut resources = Resources::default();
+        let mut apply = apply_style_tweens.system();
+        apply.initialize(&mut resources);
+
+        let mut tween = width_tween();
+        tween.tick(2.);
+        let entity = world.spawn((tween, Style::default()));
+
+        run_system(&mut apply, &mut world, &mut resources);
+        assert_eq!(world.get::<Style>(entity).unwrap().size.width, Val::Px(10.));
+
+        // a finished tween leaves later changes alone
+        world.get_mut::<Tween>(entity).unwrap().tick(1.);
+        world.get_mut::<Style>(entity).unwrap().size.width = Val::Px(3.);
+        run_system(&mut apply, &mut world, &mut resources);
+        assert_eq!(world.get::<Style>(entity).unwrap().size.width, Val::Px(3.));
+    }
+}
@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+use spectre_animations::AnimationFrameTriggered;
+
+mod mixer;
+mod rodio_backend;
+
+pub use mixer::*;
+pub use rodio_backend::*;
+
+pub mod prelude {
+    pub use crate::*;
+}
+
+/// Selects where the commands from the `AudioMixer` are played
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioBackendKind {
+    /// Plays sounds loaded by bevy's asset server on the default output device, see `RodioAudioBackend`
+    Rodio,
+
+    /// Records commands in the `NullAudioBackend` resource without playing them,
+    /// for headless games and tests
+    Null,
+}
+
+/// Request a one-shot sound on the given channel
+pub struct PlaySound {
+    pub channel: AudioChannel,
+    pub source: Handle<AudioSource>,
+}
+
+/// Request music to start playing, crossfading from the current music over `crossfade` seconds
+pub struct PlayMusic {
+    pub source: Handle<AudioSource>,
+    pub crossfade: f32,
+}
+
+/// Request the current music to fade out over `fade_out` seconds
+pub struct StopMusic {
+    pub fade_out: f32,
+}
+
+/// A component which plays sounds when animation frame triggers with a matching name fire
+pub struct AnimationSounds {
+    pub channel: AudioChannel,
+
+    /// (trigger name, sound) pairs
+    pub sounds: Vec<(String, Handle<AudioSource>)>,
+}
+
+pub struct AudioMixerPlugin {
+    pub backend: AudioBackendKind,
+}
+
+impl Default for AudioMixerPlugin {
+    fn default() -> Self {
+        AudioMixerPlugin {
+            backend: AudioBackendKind::Rodio,
+        }
+    }
+}
+
+impl Plugin for AudioMixerPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<AudioMixer>()
+            .add_event::<PlaySound>()
+            .add_event::<PlayMusic>()
+            .add_event::<StopMusic>()
+            .add_stage_after("update", "audio")
+            .add_system_to_stage("audio", audio_request_system.system())
+            .add_system_to_stage("audio", animation_sound_system.system())
+            .add_system_to_stage("audio", mixer_update_system.system())
+            // the backend runs in a later stage, so it plays every command the mixer sent this frame
+            .add_stage_after("audio", "audio_output");
+
+        match self.backend {
+            AudioBackendKind::Rodio => {
+                app.init_resource::<RodioAudioBackend>()
+                    .add_system_to_stage("audio_output", rodio_audio_backend.system());
+            }
+            AudioBackendKind::Null => {
+                app.init_resource::<NullAudioBackend>()
+                    .add_system_to_stage("audio_output", null_audio_backend.system());
+            }
+        }
+    }
+}
+
+fn audio_request_system(
+    mut mixer: ResMut<AudioMixer>,
+    mut sound_reader: Local<EventReader<PlaySound>>,
+    sounds: Res<Events<PlaySound>>,
+    mut music_reader: Local<EventReader<PlayMusic>>,
+    music: Res<Events<PlayMusic>>,
+    mut stop_reader: Local<EventReader<StopMusic>>,
+    stops: Res<Events<StopMusic>>,
+) {
+    for sound in sound_reader.iter(&sounds) {
+        mixer.play(sound.channel, sound.source);
+    }
+
+    for stop in stop_reader.iter(&stops) {
+        mixer.stop_music(stop.fade_out);
+    }
+
+    for music in music_reader.iter(&music) {
+        mixer.play_music(music.source, music.crossfade);
+    }
+}
+
+fn animation_sound_system(
+    mut mixer: ResMut<AudioMixer>,
+    mut trigger_reader: Local<EventReader<AnimationFrameTriggered>>,
+    triggers: Res<Events<AnimationFrameTriggered>>,
+    sounds: Query<&AnimationSounds>,
+) {
+    for trigger in trigger_reader.iter(&triggers) {
+        let entity_sounds = match sounds.get::<AnimationSounds>(trigger.entity) {
+            Ok(entity_sounds) => entity_sounds,
+            Err(_) => continue,
+        };
+
+        for (name, source) in entity_sounds.sounds.iter() {
+            if *name == trigger.name {
+                mixer.play(entity_sounds.channel, *source);
+            }
+        }
+    }
+}
+
+fn mixer_update_system(time: Res<Time>, mut mixer: ResMut<AudioMixer>) {
+    // music fades run on real time, so they continue while the game is paused
+    mixer.update(time.delta_seconds);
+}
+
+fn rodio_audio_backend(
+    mut mixer: ResMut<AudioMixer>,
+    mut backend: ResMut<RodioAudioBackend>,
+    sources: Res<Assets<AudioSource>>,
+) {
+    for command in mixer.take_commands() {
+        backend.apply(command);
+    }
+
+    backend.play_loaded(&sources);
+}
+
+fn null_audio_backend(mut mixer: ResMut<AudioMixer>, mut backend: ResMut<NullAudioBackend>) {
+    backend.commands.extend(mixer.take_commands());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headless_app() -> App {
+        let mut builder = App::build();
+        builder
+            .init_resource::<Time>()
+            .add_event::<AnimationFrameTriggered>()
+            .add_plugin(AudioMixerPlugin {
+                backend: AudioBackendKind::Null,
+            });
+
+        builder.app
+    }
+
+    fn played_sources(app: &App) -> Vec<(AudioChannel, Handle<AudioSource>)> {
+        let backend = app.resources.get::<NullAudioBackend>().unwrap();
+        backend
+            .commands
+            .iter()
+            .filter_map(|command| match command {
+                AudioCommand::Play {
+                    channel, source, ..
+                } => Some((*channel, *source)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plays_requests_on_the_null_backend() {
+        let mut app = headless_app();
+        let click = Handle::<AudioSource>::new();
+        let music = Handle::<AudioSource>::new();
+        let footstep = Handle::<AudioSource>::new();
+
+        let walker = app.world.spawn((AnimationSounds {
+            channel: AudioChannel::Sfx,
+            sounds: vec![("footstep".to_string(), footstep)],
+        },));
+
+        app.resources
+            .get_mut::<Events<PlaySound>>()
+            .unwrap()
+            .send(PlaySound {
+                channel: AudioChannel::Ui,
+                source: click,
+            });
+        app.resources
+            .get_mut::<Events<PlayMusic>>()
+            .unwrap()
+            .send(PlayMusic {
+                source: music,
+                crossfade: 0.,
+            });
+        app.resources
+            .get_mut::<Events<AnimationFrameTriggered>>()
+            .unwrap()
+            .send(AnimationFrameTriggered {
+                entity: walker,
+                name: "footstep".to_string(),
+            });
+        app.update();
+
+        let played = played_sources(&app);
+        assert_eq!(played.len(), 3);
+        assert!(played.contains(&(AudioChannel::Ui, click)));
+        assert!(played.contains(&(AudioChannel::Music, music)));
+        assert!(played.contains(&(AudioChannel::Sfx, footstep)));
+
+        // events are only played once
+        app.update();
+        assert_eq!(played_sources(&app).len(), 3);
+    }
+}
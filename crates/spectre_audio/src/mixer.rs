@@ -0,0 +1,280 @@
+use bevy::prelude::*;
+
+/// Identifies a sound started by the mixer
+pub type PlaybackId = usize;
+
+/// The channels sounds are played on, each channel has its own volume
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioChannel {
+    Music,
+    Sfx,
+    Ui,
+}
+
+impl AudioChannel {
+    fn index(self) -> usize {
+        match self {
+            AudioChannel::Music => 0,
+            AudioChannel::Sfx => 1,
+            AudioChannel::Ui => 2,
+        }
+    }
+}
+
+/// The commands the mixer sends to the audio backend
+#[derive(Clone, Debug, PartialEq)]
+pub enum AudioCommand {
+    Play {
+        id: PlaybackId,
+        channel: AudioChannel,
+        source: Handle<AudioSource>,
+        volume: f32,
+        looping: bool,
+    },
+    SetVolume {
+        id: PlaybackId,
+        volume: f32,
+    },
+    Stop {
+        id: PlaybackId,
+    },
+}
+
+struct MusicTrack {
+    id: PlaybackId,
+    source: Handle<AudioSource>,
+
+    /// The fade level, from 0 (silent) to 1 (full channel volume)
+    fade: f32,
+
+    /// The change in fade level per second
+    fade_rate: f32,
+}
+
+/// A resource which tracks channel volumes and music, and turns requests
+/// into `AudioCommand`s which are played by the audio backend
+pub struct AudioMixer {
+    pub master_volume: f32,
+    channel_volumes: [f32; 3],
+    next_id: PlaybackId,
+    music: Option<MusicTrack>,
+    fading_music: Vec<MusicTrack>,
+    commands: Vec<AudioCommand>,
+}
+
+impl Default for AudioMixer {
+    fn default() -> Self {
+        AudioMixer {
+            master_volume: 1.,
+            channel_volumes: [1.; 3],
+            next_id: 0,
+            music: None,
+            fading_music: Vec::default(),
+            commands: Vec::default(),
+        }
+    }
+}
+
+impl AudioMixer {
+    fn volume(&self, channel: AudioChannel, fade: f32) -> f32 {
+        self.master_volume * self.channel_volumes[channel.index()] * fade
+    }
+
+    fn start(
+        &mut self,
+        channel: AudioChannel,
+        source: Handle<AudioSource>,
+        volume: f32,
+        looping: bool,
+    ) -> PlaybackId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.commands.push(AudioCommand::Play {
+            id,
+            channel,
+            source,
+            volume,
+            looping,
+        });
+
+        id
+    }
+
+    /// Plays a one-shot sound on the given channel
+    pub fn play(&mut self, channel: AudioChannel, source: Handle<AudioSource>) -> PlaybackId {
+        let volume = self.volume(channel, 1.);
+        self.start(channel, source, volume, false)
+    }
+
+    /// Starts looping music, crossfading from the current music over the given number of seconds.
+    /// Requests for the music which is already playing are ignored.
+    pub fn play_music(&mut self, source: Handle<AudioSource>, crossfade: f32) {
+        if self.current_music() == Some(source) {
+            return;
+        }
+
+        self.stop_music(crossfade);
+
+        let fade = if crossfade > 0. { 0. } else { 1. };
+        let volume = self.volume(AudioChannel::Music, fade);
+        let id = self.start(AudioChannel::Music, source, volume, true);
+
+        self.music = Some(MusicTrack {
+            id,
+            source,
+            fade,
+            fade_rate: if crossfade > 0. { 1. / crossfade } else { 0. },
+        });
+    }
+
+    /// Fades out the current music over the given number of seconds
+    pub fn stop_music(&mut self, fade_out: f32) {
+        let mut track = match self.music.take() {
+            Some(track) => track,
+            None => return,
+        };
+
+        if fade_out <= 0. {
+            self.commands.push(AudioCommand::Stop { id: track.id });
+            return;
+        }
+
+        track.fade_rate = 1. / fade_out;
+        self.fading_music.push(track);
+    }
+
+    /// returns the music which is playing or fading in
+    pub fn current_music(&self) -> Option<Handle<AudioSource>> {
+        self.music.as_ref().map(|track| track.source)
+    }
+
+    /// returns the volume of a channel, from 0 to 1
+    pub fn channel_volume(&self, channel: AudioChannel) -> f32 {
+        self.channel_volumes[channel.index()]
+    }
+
+    /// Sets the volume of a channel, from 0 to 1, updating music which is playing
+    pub fn set_channel_volume(&mut self, channel: AudioChannel, volume: f32) {
+        self.channel_volumes[channel.index()] = volume.max(0.).min(1.);
+        self.refresh_music_volumes();
+    }
+
+    /// Sets the master volume, from 0 to 1, updating music which is playing
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume.max(0.).min(1.);
+        self.refresh_music_volumes();
+    }
+
+    fn refresh_music_volumes(&mut self) {
+        let mut commands = Vec::new();
+        for track in self.music.iter().chain(self.fading_music.iter()) {
+            commands.push(AudioCommand::SetVolume {
+                id: track.id,
+                volume: self.volume(AudioChannel::Music, track.fade),
+            });
+        }
+
+        self.commands.extend(commands);
+    }
+
+    /// Advances music fades by the given number of seconds
+    pub fn update(&mut self, delta: f32) {
+        let music_volume = self.volume(AudioChannel::Music, 1.);
+
+        if let Some(track) = self.music.as_mut() {
+            if track.fade < 1. {
+                track.fade = (track.fade + delta * track.fade_rate).min(1.);
+                self.commands.push(AudioCommand::SetVolume {
+                    id: track.id,
+                    volume: music_volume * track.fade,
+                });
+            }
+        }
+
+        let mut commands = Vec::new();
+        self.fading_music.retain(|track| track.fade > 0.);
+        for track in self.fading_music.iter_mut() {
+            track.fade = (track.fade - delta * track.fade_rate).max(0.);
+
+            if track.fade > 0. {
+                commands.push(AudioCommand::SetVolume {
+                    id: track.id,
+                    volume: music_volume * track.fade,
+                });
+            } else {
+                commands.push(AudioCommand::Stop { id: track.id });
+            }
+        }
+
+        self.commands.extend(commands);
+    }
+
+    /// Removes and returns the commands which should be sent to the audio backend
+    pub fn take_commands(&mut self) -> Vec<AudioCommand> {
+        std::mem::take(&mut self.commands)
+    }
+}
+
+/// An audio backend which records commands instead of playing them, for headless games and tests
+#[derive(Default)]
+pub struct NullAudioBackend {
+    pub commands: Vec<AudioCommand>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crossfades_music() {
+        let mut mixer = AudioMixer::default();
+        let first = Handle::<AudioSource>::new();
+        let second = Handle::<AudioSource>::new();
+
+        mixer.play_music(first, 0.);
+        assert_eq!(
+            mixer.take_commands(),
+            vec![AudioCommand::Play {
+                id: 0,
+                channel: AudioChannel::Music,
+                source: first,
+                volume: 1.,
+                looping: true,
+            }]
+        );
+
+        mixer.play_music(second, 2.);
+        mixer.update(1.);
+        let commands = mixer.take_commands();
+        assert!(commands.contains(&AudioCommand::SetVolume { id: 1, volume: 0.5 }));
+        assert!(commands.contains(&AudioCommand::SetVolume { id: 0, volume: 0.5 }));
+
+        mixer.update(1.);
+        let commands = mixer.take_commands();
+        assert!(commands.contains(&AudioCommand::SetVolume { id: 1, volume: 1. }));
+        assert!(commands.contains(&AudioCommand::Stop { id: 0 }));
+        assert_eq!(mixer.current_music(), Some(second));
+    }
+
+    #[test]
+    fn applies_channel_volumes() {
+        let mut mixer = AudioMixer::default();
+        mixer.set_channel_volume(AudioChannel::Sfx, 0.5);
+        mixer.set_master_volume(0.5);
+
+        let source = Handle::<AudioSource>::new();
+        mixer.play(AudioChannel::Sfx, source);
+
+        assert_eq!(
+            mixer.take_commands(),
+            vec![AudioCommand::Play {
+                id: 0,
+                channel: AudioChannel::Sfx,
+                source,
+                volume: 0.25,
+                looping: false,
+            }]
+        );
+    }
+}
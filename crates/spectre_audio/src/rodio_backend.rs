@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+use rodio::{Decoder, Device, Sink, Source};
+use std::{collections::HashMap, io::Cursor};
+use tracing::warn;
+
+use crate::{AudioCommand, PlaybackId};
+
+/// A sound which is waiting for its source to load
+struct PendingSound {
+    id: PlaybackId,
+    source: Handle<AudioSource>,
+    volume: f32,
+    looping: bool,
+}
+
+/// Plays the mixer's commands on the default output device. Unlike bevy's `AudioOutput`
+/// each sound is played in its own sink, so its volume can be changed and it can be stopped.
+pub struct RodioAudioBackend {
+    device: Option<Device>,
+    sinks: HashMap<PlaybackId, Sink>,
+    pending: Vec<PendingSound>,
+}
+
+impl Default for RodioAudioBackend {
+    fn default() -> Self {
+        let device = rodio::default_output_device();
+        if device.is_none() {
+            warn!("No audio output device found, sounds won't be played");
+        }
+
+        RodioAudioBackend {
+            device,
+            sinks: HashMap::default(),
+            pending: Vec::default(),
+        }
+    }
+}
+
+impl RodioAudioBackend {
+    pub fn apply(&mut self, command: AudioCommand) {
+        match command {
+            AudioCommand::Play {
+                id,
+                source,
+                volume,
+                looping,
+                ..
+            } => self.pending.push(PendingSound {
+                id,
+                source,
+                volume,
+                looping,
+            }),
+            AudioCommand::SetVolume { id, volume } => {
+                if let Some(sink) = self.sinks.get(&id) {
+                    sink.set_volume(volume);
+                } else if let Some(sound) = self.pending.iter_mut().find(|sound| sound.id == id) {
+                    sound.volume = volume;
+                }
+            }
+            AudioCommand::Stop { id } => {
+                if let Some(sink) = self.sinks.remove(&id) {
+                    sink.stop();
+                }
+                self.pending.retain(|sound| sound.id != id);
+            }
+        }
+    }
+
+    /// Starts the sounds whose source has loaded, and drops sinks which have finished playing
+    pub fn play_loaded(&mut self, sources: &Assets<AudioSource>) {
+        let device = match self.device.as_ref() {
+            Some(device) => device,
+            None => {
+                self.pending.clear();
+                return;
+            }
+        };
+
+        let mut i = 0;
+        while i < self.pending.len() {
+            let audio = match sources.get(&self.pending[i].source) {
+                Some(audio) => audio.clone(),
+                None => {
+                    i += 1;
+                    continue;
+                }
+            };
+
+            let sound = self.pending.remove(i);
+            let decoder = match Decoder::new(Cursor::new(audio)) {
+                Ok(decoder) => decoder,
+                Err(err) => {
+                    warn!(?err, "Unable to decode audio source");
+                    continue;
+                }
+            };
+
+            let sink = Sink::new(device);
+            sink.set_volume(sound.volume);
+            if sound.looping {
+                sink.append(decoder.repeat_infinite());
+            } else {
+                sink.append(decoder);
+            }
+
+            self.sinks.insert(sound.id, sink);
+        }
+
+        self.sinks.retain(|_, sink| !sink.empty());
+    }
+}
@@ -6,7 +6,8 @@ pub mod prelude {
 }
 
 /// Contains a description of a stat buff
-/// set expiry to a game time to automatically remove at that time.
+/// set expiry to a game time to automatically remove at that time, measured on the clock
+/// the entity is bound to with a `ClockBinding` (the world clock by default).
 /// set expiry to 0 to never expire
 /// set either percentage (0.1 for 10% buff, percentages add don't stack) or an absolute increase in amount.
 /// if both percentage and amount are non zero, then both will be used
@@ -18,6 +19,17 @@ pub struct Buff {
     pub amount: f32,
 }
 
+impl Buff {
+    /// A buff which expires once the clock has advanced by the given number of seconds
+    pub fn expiring(clock: &Clock, duration: f32, percentage: f32, amount: f32) -> Self {
+        Buff {
+            expiry: clock.time_after(duration),
+            percentage,
+            amount,
+        }
+    }
+}
+
 /// A statistic of an entity that that can be modified (temporarily or permanently)
 pub struct BuffableStatistic {
     pub base_value: f32,
@@ -133,6 +145,18 @@ impl Mana {
 
 pub struct Movement {
     pub movement_speed: BuffableStatistic,
+
+    /// The current velocity of the entity, also used to pick its facing
+    pub velocity: Vec2,
+}
+
+impl Movement {
+    pub fn new(movement_speed: f32) -> Self {
+        Movement {
+            movement_speed: BuffableStatistic::new(movement_speed),
+            velocity: Vec2::zero(),
+        }
+    }
 }
 
 pub struct CharacterStatsPlugin;
@@ -141,44 +165,61 @@ impl Plugin for CharacterStatsPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_stage_before("update", "update_stats")
             .add_system_to_stage("update_stats", refresh_stats.system())
-            .add_stage_after("update", "regeneration")
-            .add_system_to_stage("regeneration", health_regeneration.system())
-            .add_system_to_stage("regeneration", mana_regeneration.system());
+            // regeneration runs once per fixed step, so it is the same at any frame rate
+            .add_fixed_system(health_regeneration.system())
+            .add_fixed_system(mana_regeneration.system());
     }
 }
 
 fn refresh_stats(
-    game_time: Res<GameTime>,
-    mut stats: Mut<Stats>,
-    mut movement: Mut<Movement>,
-    mut health: Mut<Health>,
-    mut mana: Mut<Mana>,
+    clocks: Res<GameClocks>,
+    mut query: Query<(
+        &mut Stats,
+        &mut Movement,
+        &mut Health,
+        &mut Mana,
+        Option<&ClockBinding>,
+    )>,
 ) {
-    stats.update(game_time.elapsed_time);
-    movement.movement_speed.update(game_time.elapsed_time);
-    health.max_health.update(game_time.elapsed_time);
-    mana.max_mana.update(game_time.elapsed_time);
+    for (mut stats, mut movement, mut health, mut mana, binding) in &mut query.iter() {
+        let elapsed = clocks.bound(binding).map_or(0., |clock| clock.elapsed);
+        stats.update(elapsed);
+        movement.movement_speed.update(elapsed);
+        health.max_health.update(elapsed);
+        mana.max_mana.update(elapsed);
+
+        if !stats.is_changed {
+            continue;
+        }
 
-    if !stats.is_changed {
-        return;
-    }
+        movement
+            .movement_speed
+            .set_base(stats.agility.base_value * 10.);
+        health.max_health.set_base(stats.strength.base_value * 10.);
+        mana.max_mana.set_base(stats.intelligence.base_value * 10.);
 
-    movement
-        .movement_speed
-        .set_base(stats.agility.base_value * 10.);
-    health.max_health.set_base(stats.strength.base_value * 10.);
-    mana.max_mana.set_base(stats.intelligence.base_value * 10.);
+        stats.is_changed = false;
+    }
+}
 
-    stats.is_changed = false;
+fn health_regeneration(
+    game_time: Res<GameTime>,
+    clocks: Res<GameClocks>,
+    mut query: Query<(&mut Health, Option<&ClockBinding>)>,
+) {
+    for (mut health, binding) in &mut query.iter() {
+        let delta = clocks.bound_step_delta(binding, game_time.fixed_delta());
+        regenerate_health(&mut health, delta);
+    }
 }
 
-fn health_regeneration(time: Res<GameTime>, mut health: Mut<Health>) {
+fn regenerate_health(health: &mut Health, delta: f32) {
     if health.current_health < 0.5 {
         // don't regen when dead
         return;
     }
 
-    health.target_health += health.regeneration * time.delta;
+    health.target_health += health.regeneration * delta;
 
     // check target isn't above max
     if health.target_health > health.max_health.value {
@@ -193,8 +234,19 @@ fn health_regeneration(time: Res<GameTime>, mut health: Mut<Health>) {
     }
 }
 
-fn mana_regeneration(time: Res<GameTime>, mut mana: Mut<Mana>) {
-    mana.current_mana = mana.regeneration * time.delta;
+fn mana_regeneration(
+    game_time: Res<GameTime>,
+    clocks: Res<GameClocks>,
+    mut query: Query<(&mut Mana, Option<&ClockBinding>)>,
+) {
+    for (mut mana, binding) in &mut query.iter() {
+        let delta = clocks.bound_step_delta(binding, game_time.fixed_delta());
+        regenerate_mana(&mut mana, delta);
+    }
+}
+
+fn regenerate_mana(mana: &mut Mana, delta: f32) {
+    mana.current_mana += mana.regeneration * delta;
 
     // clamp health to maximum
     if mana.current_mana > mana.max_mana.value {
@@ -203,3 +255,40 @@ fn mana_regeneration(time: Res<GameTime>, mut mana: Mut<Mana>) {
         mana.current_mana = 0.;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_system(system: &mut Box<dyn System>, world: &mut World, resources: &mut Resources) {
+        system.update_archetype_access(world);
+        system.run(world, resources);
+        system.run_thread_local(world, resources);
+    }
+
+    #[test]
+    fn regenerates_mana_each_fixed_step() {
+        let mut world = World::new();
+        let mut resources = Resources::default();
+
+        let mut game_time = GameTime::default().with_tick_rate(10.);
+        let steps = game_time.fixed_step.advance(0.5);
+        resources.insert(game_time);
+        resources.insert(GameClocks::default());
+
+        let mut mana = Mana::new(100.);
+        mana.current_mana = 10.;
+        let entity = world.spawn((mana,));
+
+        let mut regeneration = mana_regeneration.system();
+        regeneration.initialize(&mut resources);
+        for _ in 0..steps {
+            run_system(&mut regeneration, &mut world, &mut resources);
+        }
+
+        // 2 mana a second over five 0.1 second steps
+        assert_eq!(steps, 5);
+        let mana = world.get::<Mana>(entity).unwrap();
+        assert!((mana.current_mana - 11.).abs() < 0.001);
+    }
+}
@@ -0,0 +1,41 @@
+use anyhow::anyhow;
+use bevy::{asset::AssetLoader, prelude::*};
+use std::path::Path;
+
+/// Loads wav files as audio sources, bevy only has a loader for mp3 files.
+/// The files are decoded when they're played, so this only checks the header.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WavLoader;
+
+impl AssetLoader<AudioSource> for WavLoader {
+    fn from_bytes(&self, asset_path: &Path, bytes: Vec<u8>) -> Result<AudioSource, anyhow::Error> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(anyhow!("{:?} isn't a wav file", asset_path));
+        }
+
+        Ok(AudioSource {
+            bytes: bytes.into(),
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["wav"];
+        EXTENSIONS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_wav_files() {
+        let bytes = include_bytes!("../../../assets/music/menu.wav").to_vec();
+        let path = Path::new("menu.wav");
+
+        assert!(WavLoader.from_bytes(path, bytes).is_ok());
+        assert!(WavLoader
+            .from_bytes(path, b"ID3 not a wav".to_vec())
+            .is_err());
+    }
+}
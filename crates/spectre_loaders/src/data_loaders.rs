@@ -1,33 +1,299 @@
 use bevy::asset::AssetLoader;
-use ron::de::from_bytes;
-use serde::de::Deserialize;
-use std::path::Path;
-
-/// A generic data file loader which loads RON files from the assets folder
-/// and deserializes them into the provided type. There should be a 1:1 mapping
-/// between the file extension set and the type of asset loaded
-#[derive(Default)]
+use serde::{de::Deserialize, Serialize};
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    fmt,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::{collect_references, load_data_file, DataAsset, DataDependencies};
+
+/// An error raised when a data file can't be parsed, with the location of the error in the file
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataFileError {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DataFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // errors found after parsing, e.g. by validation, have no location
+        if self.line == 0 {
+            return write!(f, "{}: {}", self.path, self.message);
+        }
+
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.path, self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for DataFileError {}
+
+/// Collects the errors raised by data file loaders, which run on the asset server's
+/// worker threads. The errors are reported and sent as `DataFileError` events by
+/// the `ResourceLoaderPlugin`.
+#[derive(Clone, Default)]
+pub struct DataFileErrors {
+    errors: Arc<Mutex<Vec<DataFileError>>>,
+}
+
+impl DataFileErrors {
+    pub fn push(&self, error: DataFileError) {
+        self.errors.lock().unwrap().push(error);
+    }
+
+    /// Removes and returns the errors raised since the last call
+    pub fn take(&self) -> Vec<DataFileError> {
+        std::mem::take(&mut *self.errors.lock().unwrap())
+    }
+}
+
+/// Records the asset type of each data file as its loader reads it. Data files in a manifest
+/// are loaded without a type, so this lets `NamedAssets` check the type once they've loaded.
+#[derive(Clone, Default)]
+pub struct DataFileTypes {
+    types: Arc<Mutex<HashMap<String, TypeId>>>,
+}
+
+impl DataFileTypes {
+    pub fn insert(&self, path: &Path, type_id: TypeId) {
+        self.types
+            .lock()
+            .unwrap()
+            .insert(path.to_string_lossy().to_string(), type_id);
+    }
+
+    /// returns the type of the data file loaded from the given path. The asset server may
+    /// report a longer path than was requested, so paths are matched by their ending.
+    pub fn get(&self, path: &str) -> Option<TypeId> {
+        self.types
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(loaded, _)| Path::new(loaded).ends_with(path))
+            .map(|(_, type_id)| *type_id)
+    }
+}
+
+/// Deserializes a RON data file, returning the line and column of any parse error
+pub fn parse_ron<TAsset>(path: &Path, bytes: &[u8]) -> Result<TAsset, DataFileError>
+where
+    for<'de> TAsset: Deserialize<'de>,
+{
+    ron::de::from_bytes::<TAsset>(bytes).map_err(|err| DataFileError {
+        path: path.to_string_lossy().to_string(),
+        line: err.position.line,
+        column: err.position.col,
+        message: err.code.to_string(),
+    })
+}
+
+/// The file formats data assets can be loaded from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DataFormat {
+    Ron,
+    Json,
+    Toml,
+
+    /// A compact binary format for shipping builds, see `DataFileRegistry::convert_directory`.
+    /// Binary files are written by the converter so they are always the current version.
+    Bincode,
+}
+
+impl DataFormat {
+    /// returns true if the format describes its own structure, so it can be
+    /// read without knowing the type (which is required for migrations)
+    pub fn is_self_describing(self) -> bool {
+        self != DataFormat::Bincode
+    }
+
+    /// Deserializes a data file, returning the line and column of any parse error if the format has them
+    pub fn parse<TAsset>(self, path: &Path, bytes: &[u8]) -> Result<TAsset, DataFileError>
+    where
+        for<'de> TAsset: Deserialize<'de>,
+    {
+        let error = |line: usize, column: usize, message: String| DataFileError {
+            path: path.to_string_lossy().to_string(),
+            line,
+            column,
+            message,
+        };
+
+        match self {
+            DataFormat::Ron => parse_ron(path, bytes),
+            DataFormat::Json => serde_json::from_slice(bytes)
+                .map_err(|err| error(err.line(), err.column(), err.to_string())),
+            DataFormat::Toml => {
+                let text =
+                    std::str::from_utf8(bytes).map_err(|err| error(0, 0, err.to_string()))?;
+                toml::from_str(text).map_err(|err| {
+                    // toml locations are zero based
+                    let (line, column) = err
+                        .line_col()
+                        .map(|(line, column)| (line + 1, column + 1))
+                        .unwrap_or((0, 0));
+                    error(line, column, err.to_string())
+                })
+            }
+            DataFormat::Bincode => {
+                bincode::deserialize(bytes).map_err(|err| error(0, 0, err.to_string()))
+            }
+        }
+    }
+
+    /// Serializes a value in the format
+    pub fn serialize<TAsset: Serialize>(self, asset: &TAsset) -> Result<Vec<u8>, anyhow::Error> {
+        Ok(match self {
+            DataFormat::Ron => ron::ser::to_string(asset)?.into_bytes(),
+            DataFormat::Json => serde_json::to_vec(asset)?,
+            DataFormat::Toml => toml::to_vec(asset)?,
+            DataFormat::Bincode => bincode::serialize(asset)?,
+        })
+    }
+}
+
+/// A generic data file loader which loads data files from the assets folder
+/// and deserializes them into the provided type, migrating older files and
+/// validating the result. Each extension is read in its own format. There
+/// should be a 1:1 mapping between the file extension set and the type of
+/// asset loaded
+#[derive(Clone, Default)]
 pub struct DataFileLoader {
     matching_extensions: Vec<&'static str>,
+    formats: Vec<DataFormat>,
+    errors: Option<DataFileErrors>,
+    dependencies: Option<DataDependencies>,
+    types: Option<DataFileTypes>,
 }
 
 impl DataFileLoader {
+    /// Creates a loader which reads files with the given extensions as RON
     pub fn from_extensions(matching_extensions: Vec<&'static str>) -> Self {
         DataFileLoader {
+            formats: vec![DataFormat::Ron; matching_extensions.len()],
             matching_extensions,
+            errors: None,
+            dependencies: None,
+            types: None,
         }
     }
+
+    /// Reads files with the given extension in the given format
+    pub fn with_format(mut self, extension: &'static str, format: DataFormat) -> Self {
+        self.matching_extensions.push(extension);
+        self.formats.push(format);
+        self
+    }
+
+    /// returns the format files with the given path are read in
+    pub fn format(&self, path: &Path) -> DataFormat {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        self.matching_extensions
+            .iter()
+            .position(|matching| *matching == extension)
+            .map(|index| self.formats[index])
+            .unwrap_or(DataFormat::Ron)
+    }
+
+    /// Reports parse errors to the given collector, usually the `DataFileErrors` resource,
+    /// so that errors in files edited while the game is running are shown
+    pub fn with_errors(mut self, errors: &DataFileErrors) -> Self {
+        self.errors = Some(errors.clone());
+        self
+    }
+
+    /// Reports the `AssetRef`s in loaded files to the given collector, usually the
+    /// `DataDependencies` resource, so the referenced assets are loaded automatically
+    pub fn with_dependencies(mut self, dependencies: &DataDependencies) -> Self {
+        self.dependencies = Some(dependencies.clone());
+        self
+    }
+
+    /// Records the type of loaded files in the given collector, usually the `DataFileTypes`
+    /// resource, so data assets named in a manifest are only returned as their own type
+    pub fn with_types(mut self, types: &DataFileTypes) -> Self {
+        self.types = Some(types.clone());
+        self
+    }
 }
 
-impl<TAsset> AssetLoader<TAsset> for DataFileLoader
-where
-    for<'de> TAsset: Deserialize<'de>,
-{
-    fn from_bytes(&self, _asset_path: &Path, bytes: Vec<u8>) -> Result<TAsset, anyhow::Error> {
-        Ok(from_bytes::<TAsset>(bytes.as_slice())?)
+impl<TAsset: DataAsset> AssetLoader<TAsset> for DataFileLoader {
+    fn from_bytes(&self, asset_path: &Path, bytes: Vec<u8>) -> Result<TAsset, anyhow::Error> {
+        let (result, references) = collect_references(|| {
+            load_data_file::<TAsset>(asset_path, bytes.as_slice(), self.format(asset_path))
+        });
+
+        match result {
+            Ok(asset) => {
+                if let Some(types) = &self.types {
+                    types.insert(asset_path, TypeId::of::<TAsset>());
+                }
+
+                if let Some(dependencies) = &self.dependencies {
+                    if !references.is_empty() {
+                        dependencies.push(asset_path, references);
+                    }
+                }
+
+                Ok(asset)
+            }
+            Err(err) => {
+                if let Some(errors) = &self.errors {
+                    errors.push(err.clone());
+                }
+
+                Err(err.into())
+            }
+        }
     }
 
     fn extensions(&self) -> &[&str] {
         self.matching_extensions.as_slice()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Item {
+        value: i32,
+    }
+
+    impl DataAsset for Item {}
+
+    #[test]
+    fn reports_error_location() {
+        let err = parse_ron::<Item>(Path::new("item.ron"), b"(\n  value: \"ten\",\n)").unwrap_err();
+
+        assert_eq!(err.path, "item.ron");
+        assert_eq!(err.line, 2);
+        assert!(err.column > 1);
+    }
+
+    #[test]
+    fn records_the_type_of_loaded_files() {
+        let types = DataFileTypes::default();
+        let loader = DataFileLoader::from_extensions(vec!["item"]).with_types(&types);
+
+        let item: Item = loader
+            .from_bytes(Path::new("assets/data/sword.item"), b"(value: 10)".to_vec())
+            .unwrap();
+        assert_eq!(item.value, 10);
+
+        assert_eq!(types.get("data/sword.item"), Some(TypeId::of::<Item>()));
+        assert_eq!(types.get("data/shield.item"), None);
+    }
+}
@@ -0,0 +1,212 @@
+use bevy::{asset::HandleId, prelude::*};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+use crate::{AssetPacks, LoaderAssetType, LoadingProgressData};
+
+/// Requests every file matching a pattern, e.g. `assets/data/items/*.item`.
+/// Only the file name part of the pattern may contain wildcards (`*` and `?`),
+/// a pattern which is a directory matches every file in the directory.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DirectoryDescriptor {
+    pub pattern: String,
+
+    /// If true, files in subdirectories which match the file name pattern are included
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+impl DirectoryDescriptor {
+    pub fn new(pattern: &str, recursive: bool) -> Self {
+        DirectoryDescriptor {
+            pattern: String::from(pattern),
+            recursive,
+        }
+    }
+
+    /// returns the loading data for every matching file on disk or in a pack,
+    /// which are added to the given collection in `AssetCollections`
+    pub fn expand(&self, collection: &str, packs: &AssetPacks) -> Vec<LoadingProgressData> {
+        let pattern_path = Path::new(&self.pattern);
+        let (base, file_pattern) = if pattern_path.is_dir() {
+            (pattern_path.to_path_buf(), String::from("*"))
+        } else {
+            let base = pattern_path
+                .parent()
+                .map(|parent| parent.to_path_buf())
+                .unwrap_or_default();
+            let file_pattern = pattern_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| String::from("*"));
+            (base, file_pattern)
+        };
+
+        let mut files = Vec::new();
+        find_files(&base, &file_pattern, self.recursive, &mut files);
+        files.extend(
+            packs
+                .find_files(&base, &file_pattern, self.recursive)
+                .iter()
+                .map(PathBuf::from),
+        );
+        files.sort();
+        files.dedup();
+
+        files
+            .iter()
+            .map(|file| LoadingProgressData {
+                asset_type: LoaderAssetType::Collection(
+                    String::from(collection),
+                    collection_key(&base, file),
+                ),
+                ..LoadingProgressData::from(file.to_string_lossy().as_ref())
+            })
+            .collect()
+    }
+}
+
+/// The key of a file in a collection, which is its path relative to the
+/// pattern's directory without the extension, e.g. `weapons/sword`
+fn collection_key(base: &Path, file: &Path) -> String {
+    let relative = file.strip_prefix(base).unwrap_or(file);
+    let key = relative.with_extension("");
+
+    key.components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn find_files(directory: &Path, file_pattern: &str, recursive: bool, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(err) => {
+            warn!(?directory, %err, "Unable to read directory");
+            return;
+        }
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+
+        if path.is_dir() {
+            if recursive {
+                find_files(&path, file_pattern, recursive, files);
+            }
+            continue;
+        }
+
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if wildcard_match(file_pattern, &file_name) {
+            files.push(path);
+        }
+    }
+}
+
+/// returns true if the name matches the pattern, where `*` matches
+/// any number of characters and `?` matches a single character
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // let the last star consume one more character
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// A resource which stores the handles of assets loaded from directories, by collection name and key
+#[derive(Default)]
+pub struct AssetCollections {
+    collections: HashMap<String, HashMap<String, HandleId>>,
+}
+
+impl AssetCollections {
+    /// Adds a handle to a collection, replacing any handle with the same key
+    pub fn insert(&mut self, collection: &str, key: &str, handle: HandleId) {
+        self.collections
+            .entry(collection.to_string())
+            .or_default()
+            .insert(key.to_string(), handle);
+    }
+
+    /// Removes the handle with the given key from a collection
+    pub fn remove(&mut self, collection: &str, key: &str) {
+        if let Some(items) = self.collections.get_mut(collection) {
+            items.remove(key);
+        }
+    }
+
+    /// returns the handle with the given key in a collection
+    pub fn get<T>(&self, collection: &str, key: &str) -> Option<Handle<T>> {
+        self.collections
+            .get(collection)
+            .and_then(|items| items.get(key))
+            .map(|handle| Handle::from(*handle))
+    }
+
+    /// returns the keys and handles of every item in a collection, sorted by key
+    pub fn iter<T>(&self, collection: &str) -> Vec<(String, Handle<T>)> {
+        let mut items: Vec<(String, Handle<T>)> = self
+            .collections
+            .get(collection)
+            .map(|items| {
+                items
+                    .iter()
+                    .map(|(key, handle)| (key.clone(), Handle::from(*handle)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(wildcard_match("*.item", "sword.item"));
+        assert!(wildcard_match("*", "sword.item"));
+        assert!(wildcard_match("sw?rd.*", "sword.item"));
+        assert!(wildcard_match("*o*.item", "sword.item"));
+        assert!(!wildcard_match("*.item", "sword.item.bak"));
+        assert!(!wildcard_match("*.chd", "sword.item"));
+        assert!(!wildcard_match("sword", "sword.item"));
+    }
+
+    #[test]
+    fn keys_are_relative_without_extension() {
+        let key = collection_key(
+            Path::new("assets/data/items"),
+            Path::new("assets/data/items/weapons/sword.item"),
+        );
+        assert_eq!(key, "weapons/sword");
+    }
+}
@@ -0,0 +1,154 @@
+use bevy::{prelude::*, render::texture::TextureFormat};
+
+use crate::{AssetKind, LoaderAssetType, LoadingProgressData, NamedAssets};
+
+/// Controls how many times a failed asset load is retried
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetryPolicy {
+    pub max_retries: usize,
+}
+
+impl RetryPolicy {
+    /// returns true if an asset which failed on the given attempt should be loaded again,
+    /// so each asset is attempted up to `max_retries + 1` times
+    pub fn should_retry(&self, attempts: usize) -> bool {
+        attempts <= self.max_retries
+    }
+}
+
+/// An event sent when an asset fails to load and will not be retried
+pub struct AssetLoadFailed {
+    pub path: String,
+
+    /// The `LoadAssets` group the asset was requested in
+    pub group: String,
+    pub error: String,
+
+    /// True if the asset was replaced by a placeholder from `FallbackAssets`
+    pub used_fallback: bool,
+}
+
+/// The font used in place of fonts which fail to load. This is a small pixel font which is
+/// built into the crate and not shared with any game assets, so it can't fail with them.
+const FALLBACK_FONT: &[u8] = include_bytes!("../assets/fallback_font.ttf");
+
+/// A resource holding placeholder assets which are used in place of assets that fail to load.
+/// By default this contains a magenta texture and a built in font, set the audio to use a fallback for it.
+pub struct FallbackAssets {
+    pub texture: Option<Handle<Texture>>,
+    pub font: Option<Handle<Font>>,
+    pub audio: Option<Handle<AudioSource>>,
+}
+
+impl FromResources for FallbackAssets {
+    fn from_resources(resources: &Resources) -> Self {
+        let mut textures = resources.get_mut::<Assets<Texture>>().unwrap();
+
+        // fonts can only be added when the text plugin is in use
+        let font = resources.get_mut::<Assets<Font>>().and_then(|mut fonts| {
+            Font::try_from_bytes(FALLBACK_FONT.to_vec())
+                .ok()
+                .map(|font| fonts.add(font))
+        });
+
+        FallbackAssets {
+            texture: Some(textures.add(magenta_texture())),
+            font,
+            audio: None,
+        }
+    }
+}
+
+/// Creates a small, obviously wrong magenta texture
+fn magenta_texture() -> Texture {
+    let size = 4;
+    let data = [255u8, 0, 255, 255]
+        .iter()
+        .cycle()
+        .take(size * size * 4)
+        .cloned()
+        .collect();
+
+    Texture::new(
+        Vec2::new(size as f32, size as f32),
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+/// Replaces a failed asset with a fallback, if one is available for its type.
+/// Returns true if a fallback was used.
+pub(crate) fn apply_fallback(
+    asset: &LoadingProgressData,
+    fallbacks: &FallbackAssets,
+    named_assets: &mut NamedAssets,
+) -> bool {
+    match &asset.asset_type {
+        LoaderAssetType::Untyped | LoaderAssetType::Collection(_, _) => false,
+        LoaderAssetType::Named(kind, name) => {
+            let used_fallback = match kind {
+                AssetKind::Texture => fallbacks
+                    .texture
+                    .map(|handle| named_assets.insert(name, handle))
+                    .is_some(),
+                AssetKind::Font => fallbacks
+                    .font
+                    .map(|handle| named_assets.insert(name, handle))
+                    .is_some(),
+                AssetKind::Audio => fallbacks
+                    .audio
+                    .map(|handle| named_assets.insert(name, handle))
+                    .is_some(),
+                AssetKind::Data(_) => false,
+            };
+
+            // don't leave a handle to an asset that will never load
+            if !used_fallback {
+                named_assets.remove(name);
+            }
+
+            used_fallback
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_retries() {
+        let policy = RetryPolicy { max_retries: 2 };
+
+        assert!(policy.should_retry(1));
+        assert!(policy.should_retry(2));
+        assert!(!policy.should_retry(3));
+        assert!(!RetryPolicy::default().should_retry(1));
+    }
+
+    #[test]
+    fn parses_the_fallback_font() {
+        assert!(Font::try_from_bytes(FALLBACK_FONT.to_vec()).is_ok());
+    }
+
+    #[test]
+    fn uses_fallbacks_for_named_assets() {
+        let fallbacks = FallbackAssets {
+            texture: None,
+            font: Some(Handle::<Font>::new()),
+            audio: None,
+        };
+        let mut named_assets = NamedAssets::default();
+        named_assets.insert("ui_font", Handle::<Font>::new());
+        named_assets.insert("music", Handle::<AudioSource>::new());
+
+        let font = LoadingProgressData::named("missing.ttf", AssetKind::Font, "ui_font");
+        assert!(apply_fallback(&font, &fallbacks, &mut named_assets));
+        assert_eq!(named_assets.get_by_name::<Font>("ui_font"), fallbacks.font);
+
+        // assets without a fallback are removed, rather than left with a handle that never loads
+        let music = LoadingProgressData::named("missing.wav", AssetKind::Audio, "music");
+        assert!(!apply_fallback(&music, &fallbacks, &mut named_assets));
+        assert!(!named_assets.contains("music"));
+    }
+}
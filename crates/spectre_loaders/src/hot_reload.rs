@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+use std::marker::PhantomData;
+use tracing::warn;
+
+/// Watches loaded asset files and reloads them when they change on disk.
+/// Add this plugin before any assets are loaded, as only files loaded
+/// after the watcher starts are watched.
+pub struct HotReloadPlugin;
+
+impl Plugin for HotReloadPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let asset_server = app
+            .resources()
+            .get::<AssetServer>()
+            .expect("The AssetPlugin must be added before the HotReloadPlugin");
+        if let Err(err) = asset_server.watch_for_changes() {
+            warn!(?err, "Unable to watch assets for changes");
+        }
+    }
+}
+
+/// A component which records the data asset an entity was spawned from
+pub struct DataSource<TAsset: 'static> {
+    pub handle: Handle<TAsset>,
+}
+
+impl<TAsset> DataSource<TAsset> {
+    pub fn new(handle: Handle<TAsset>) -> Self {
+        DataSource { handle }
+    }
+}
+
+/// Implemented by components which are created from a data asset,
+/// so they can be updated when the asset is reloaded
+pub trait FromDataAsset<TAsset>: Send + Sync + 'static {
+    fn apply_data(&mut self, asset: &TAsset);
+}
+
+/// Updates `TComponent` on every entity with a `DataSource<TAsset>`
+/// when the source asset is modified, e.g. by hot reloading
+pub struct DataReloadPlugin<TAsset, TComponent> {
+    marker: PhantomData<fn() -> (TAsset, TComponent)>,
+}
+
+impl<TAsset, TComponent> Default for DataReloadPlugin<TAsset, TComponent> {
+    fn default() -> Self {
+        DataReloadPlugin {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<TAsset, TComponent> Plugin for DataReloadPlugin<TAsset, TComponent>
+where
+    TAsset: Send + Sync + 'static,
+    TComponent: FromDataAsset<TAsset>,
+{
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(reload_data_components::<TAsset, TComponent>.system());
+    }
+}
+
+fn reload_data_components<TAsset, TComponent>(
+    mut event_reader: Local<EventReader<AssetEvent<TAsset>>>,
+    events: Res<Events<AssetEvent<TAsset>>>,
+    assets: Res<Assets<TAsset>>,
+    mut query: Query<(&DataSource<TAsset>, &mut TComponent)>,
+) where
+    TAsset: Send + Sync + 'static,
+    TComponent: FromDataAsset<TAsset>,
+{
+    for event in event_reader.iter(&events) {
+        let handle = match event {
+            AssetEvent::Modified { handle } => handle,
+            _ => continue,
+        };
+
+        let asset = match assets.get(handle) {
+            Some(asset) => asset,
+            None => continue,
+        };
+
+        for (source, mut component) in &mut query.iter() {
+            if source.handle == *handle {
+                component.apply_data(asset);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct Item {
+        value: i32,
+    }
+
+    impl FromDataAsset<Item> for Item {
+        fn apply_data(&mut self, asset: &Item) {
+            *self = asset.clone();
+        }
+    }
+
+    fn run_system(system: &mut Box<dyn System>, world: &mut World, resources: &mut Resources) {
+        system.update_archetype_access(world);
+        system.run(world, resources);
+        system.run_thread_local(world, resources);
+    }
+
+    #[test]
+    fn updates_components_when_their_asset_changes() {
+        let mut world = World::new();
+        let mut resources = Resources::default();
+
+        let mut items = Assets::<Item>::default();
+        let changed = items.add(Item { value: 1 });
+        let unchanged = items.add(Item { value: 2 });
+        resources.insert(items);
+        resources.insert(Events::<AssetEvent<Item>>::default());
+
+        let entity = world.spawn((DataSource::new(changed), Item { value: 1 }));
+        let other = world.spawn((DataSource::new(unchanged), Item { value: 2 }));
+
+        let mut asset_events = Assets::<Item>::asset_event_system.system();
+        let mut reload = reload_data_components::<Item, Item>.system();
+        asset_events.initialize(&mut resources);
+        reload.initialize(&mut resources);
+
+        resources
+            .get_mut::<Assets<Item>>()
+            .unwrap()
+            .set(changed, Item { value: 10 });
+        run_system(&mut asset_events, &mut world, &mut resources);
+        run_system(&mut reload, &mut world, &mut resources);
+
+        assert_eq!(world.get::<Item>(entity).unwrap().value, 10);
+        assert_eq!(world.get::<Item>(other).unwrap().value, 2);
+    }
+}
@@ -0,0 +1,72 @@
+use std::{any::TypeId, fmt, marker::PhantomData};
+
+/// A typed name for an asset registered in `NamedAssets`. Keys are usually
+/// declared as constants with the `asset_key!` macro:
+///
+/// ```ignore
+/// const WALK_SHEET: AssetKey<Texture> = asset_key!("walk_sheet");
+/// ```
+pub struct AssetKey<T> {
+    pub name: &'static str,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> AssetKey<T> {
+    pub const fn new(name: &'static str) -> Self {
+        AssetKey {
+            name,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: 'static> AssetKey<T> {
+    /// returns the type of asset the key refers to
+    pub fn type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+}
+
+impl<T> Clone for AssetKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for AssetKey<T> {}
+
+impl<T> fmt::Debug for AssetKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AssetKey({})", self.name)
+    }
+}
+
+/// Declares a typed asset key, e.g. `const UI_FONT: AssetKey<Font> = asset_key!("ui_font");`
+#[macro_export]
+macro_rules! asset_key {
+    ($name:expr) => {
+        $crate::AssetKey::new($name)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AssetKind, NamedAssets};
+    use bevy::prelude::*;
+
+    use super::*;
+
+    const SHEET: AssetKey<Texture> = asset_key!("sheet");
+    const SHEET_AS_FONT: AssetKey<Font> = asset_key!("sheet");
+
+    #[test]
+    fn resolves_keys_by_type() {
+        let mut named_assets = NamedAssets::default();
+        let handle = Handle::<Texture>::new();
+        named_assets.insert(SHEET.name, handle);
+
+        assert_eq!(named_assets.get(SHEET), Some(handle));
+        assert_eq!(named_assets.get(SHEET_AS_FONT), None);
+        assert_eq!(AssetKind::of::<Font>(), AssetKind::Font);
+    }
+}
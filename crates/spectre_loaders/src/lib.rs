@@ -1,28 +1,181 @@
-use bevy::{asset::Handle, asset::HandleId, asset::LoadState, prelude::*};
+use bevy::{
+    asset::Handle, asset::HandleId, asset::LoadState, audio::Mp3Loader, prelude::*,
+    render::texture::ImageTextureLoader, text::FontLoader,
+};
+use std::collections::HashMap;
+use tracing::{error, info, trace, warn};
 
+use data_loaders::{DataFileError, DataFileErrors, DataFileTypes};
+
+mod audio;
 pub mod data_loaders;
+mod directories;
+mod failures;
+mod hot_reload;
+mod keys;
+mod manifest;
+mod packs;
+mod references;
+#[cfg(test)]
+mod test_directory;
+mod validation;
+
+pub use audio::*;
+pub use directories::*;
+pub use failures::*;
+pub use hot_reload::*;
+pub use keys::*;
+pub use manifest::*;
+pub use packs::*;
+pub use references::*;
+pub use validation::*;
 
 pub struct ResourceLoaderPlugin;
 
 impl Plugin for ResourceLoaderPlugin {
     fn build(&self, app: &mut AppBuilder) {
+        // games add their packs before the plugin, so don't replace them
+        if app.resources().get::<AssetPacks>().is_none() {
+            app.init_resource::<AssetPacks>();
+        }
+
+        // packed files use the same loaders as bevy's asset server
+        app.add_packed_asset_loader::<Texture, _>(ImageTextureLoader::default())
+            .add_packed_asset_loader::<Font, _>(FontLoader::default())
+            .add_packed_asset_loader::<AudioSource, _>(Mp3Loader::default())
+            .add_packed_asset_loader::<AudioSource, _>(WavLoader);
+
+        // bevy only loads mp3 audio
+        app.add_asset_loader::<AudioSource, WavLoader>();
+
         app.add_resource(LoadingStatus::default())
-            .add_system(asset_loading_system.system());
+            .init_resource::<NamedAssets>()
+            .init_resource::<AssetCollections>()
+            .init_resource::<FallbackAssets>()
+            .init_resource::<DataFileErrors>()
+            .init_resource::<DataFileTypes>()
+            .init_resource::<DataDependencies>()
+            .add_event::<AssetGroupLoaded>()
+            .add_event::<AssetLoadFailed>()
+            .add_event::<DataFileError>()
+            .add_system(asset_loading_system.system())
+            .add_system(load_packed_assets.thread_local_system())
+            .add_system(data_file_error_system.system());
+    }
+}
+
+/// The group name used by `LoadAssets` requests which don't set a group
+pub const DEFAULT_ASSET_GROUP: &str = "default";
+
+/// Tracks the loading progress of a single asset group
+#[derive(Clone, Debug, Default)]
+pub struct GroupProgress {
+    /// A human readable name for the group, e.g. "level 2"
+    pub label: String,
+    pub items_loaded: usize,
+    pub items_to_load: usize,
+    pub bytes_loaded: u64,
+    pub bytes_to_load: u64,
+
+    /// The number of items which failed to load, including those replaced by a fallback
+    pub items_failed: usize,
+    pub items_using_fallback: usize,
+    pub is_complete: bool,
+}
+
+impl GroupProgress {
+    /// returns the progress from 0 to 1, weighted by file size
+    pub fn progress(&self) -> f32 {
+        // failed items count as loaded, so completed groups are always at 100%
+        if self.is_complete {
+            return 1.;
+        }
+
+        if self.bytes_to_load == 0 {
+            return 0.;
+        }
+
+        self.bytes_loaded as f32 / self.bytes_to_load as f32
+    }
+
+    /// returns the number of items which have loaded or failed
+    pub fn items_finished(&self) -> usize {
+        self.items_loaded + self.items_failed
+    }
+
+    /// returns true if any items failed to load and could not be replaced by a fallback
+    pub fn has_errors(&self) -> bool {
+        self.items_failed > self.items_using_fallback
+    }
+
+    /// returns the progress as a whole percentage, weighted by file size
+    pub fn percentage(&self) -> u32 {
+        (self.progress() * 100.).floor() as u32
     }
 }
 
+/// A resource which tracks the loading progress of all assets requested through `LoadAssets`
 #[derive(Default)]
 pub struct LoadingStatus {
     pub items_loaded: usize,
     pub items_to_load: usize,
-    pub initial_load_done: bool,
+
+    /// The number of items which failed to load, including those replaced by a fallback
+    pub items_failed: usize,
+    pub items_using_fallback: usize,
+
+    /// progress for each asset group, by group name
+    pub groups: HashMap<String, GroupProgress>,
+
+    /// The name of the most recently requested group which is still loading
+    pub current_phase: Option<String>,
+}
+
+impl LoadingStatus {
+    /// returns the progress of the named group, if it has been requested
+    pub fn group(&self, group: &str) -> Option<&GroupProgress> {
+        self.groups.get(group)
+    }
+
+    /// returns true if the named group has been requested and all its assets are loaded
+    pub fn is_group_loaded(&self, group: &str) -> bool {
+        self.groups
+            .get(group)
+            .map(|progress| progress.is_complete)
+            .unwrap_or(false)
+    }
+
+    /// returns true if any requested assets are still loading
+    pub fn is_loading(&self) -> bool {
+        self.items_loaded + self.items_failed < self.items_to_load
+    }
+
+    /// returns true if any items failed to load and could not be replaced by a fallback
+    pub fn has_errors(&self) -> bool {
+        self.items_failed > self.items_using_fallback
+    }
+
+    /// returns the progress of the group currently being loaded
+    pub fn current_phase_progress(&self) -> Option<&GroupProgress> {
+        self.current_phase
+            .as_ref()
+            .and_then(|group| self.groups.get(group))
+    }
+}
+
+/// An event sent when every asset in a group has finished loading
+pub struct AssetGroupLoaded {
+    pub group: String,
 }
 
 pub enum LoaderAssetType {
     Untyped,
-    // TODO: UntypedDirectory,
-    // TODO: AudioWithId(u128),
-    TextureWithId(u128),
+
+    /// Registers the handle in `NamedAssets` under the given name
+    Named(AssetKind, String),
+
+    /// Adds the handle to a collection in `AssetCollections` (collection, key)
+    Collection(String, String),
 }
 
 /// A struct used to internally track texture loading progress.
@@ -31,33 +184,51 @@ pub struct LoadingProgressData {
     /// The path to load the asset from
     pub path: String,
 
-    /// The type of asset to load. Currently only textures with an ID are separately defined
+    /// How the loaded handle is registered, if at all
     pub asset_type: LoaderAssetType,
 
     /// the texture handle (used to track loading progress)
     handle: Option<HandleId>,
 
+    /// the size of the file in bytes, used to weight loading progress
+    size: u64,
+
+    /// the number of times loading has been attempted
+    attempts: usize,
+
     /// Is set to true when the linked assets are fully loaded
     pub is_loaded: bool,
 }
 
-impl From<&str> for LoadingProgressData {
-    fn from(path: &str) -> Self {
+impl LoadingProgressData {
+    /// Creates loading data for an asset which is registered in `NamedAssets` by name
+    pub fn named(path: &str, kind: AssetKind, name: &str) -> Self {
         LoadingProgressData {
             path: String::from(path),
-            asset_type: LoaderAssetType::Untyped,
+            asset_type: LoaderAssetType::Named(kind, String::from(name)),
             handle: None,
+            size: 0,
+            attempts: 0,
             is_loaded: false,
         }
     }
 }
 
-impl From<(&str, u128)> for LoadingProgressData {
-    fn from(data: (&str, u128)) -> Self {
+impl LoadingProgressData {
+    /// Creates loading data for an asset which is registered in `NamedAssets` under the key
+    pub fn keyed<T: 'static>(path: &str, key: AssetKey<T>) -> Self {
+        LoadingProgressData::named(path, AssetKind::of::<T>(), key.name)
+    }
+}
+
+impl From<&str> for LoadingProgressData {
+    fn from(path: &str) -> Self {
         LoadingProgressData {
-            path: String::from(data.0),
-            asset_type: LoaderAssetType::TextureWithId(data.1),
+            path: String::from(path),
+            asset_type: LoaderAssetType::Untyped,
             handle: None,
+            size: 0,
+            attempts: 0,
             is_loaded: false,
         }
     }
@@ -65,84 +236,435 @@ impl From<(&str, u128)> for LoadingProgressData {
 
 /// Stores paths to textures which will be loaded by the asset_loading_system
 pub struct LoadAssets {
+    /// The group the assets are tracked in by `LoadingStatus`
+    pub group: String,
+
+    /// A human readable name for the group, shown while loading
+    pub label: String,
+
     pub assets: Vec<LoadingProgressData>,
+
+    /// Named texture atlases which are built once their texture is loaded
+    pub atlases: Vec<(String, AtlasDescriptor)>,
+
+    /// Directories or patterns which are expanded into assets, by collection name
+    pub directories: Vec<(String, DirectoryDescriptor)>,
+
+    /// How failed loads are retried before falling back to a placeholder
+    pub retry_policy: RetryPolicy,
+}
+
+impl From<Vec<LoadingProgressData>> for LoadAssets {
+    fn from(assets: Vec<LoadingProgressData>) -> Self {
+        LoadAssets {
+            group: String::from(DEFAULT_ASSET_GROUP),
+            label: String::from(DEFAULT_ASSET_GROUP),
+            assets,
+            atlases: Vec::default(),
+            directories: Vec::default(),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+}
+
+impl LoadAssets {
+    /// Tracks the assets in the given group rather than the default group
+    pub fn with_group(mut self, group: &str, label: &str) -> Self {
+        self.group = String::from(group);
+        self.label = String::from(label);
+        self
+    }
+
+    /// Loads every file matching the pattern into the named collection in `AssetCollections`
+    pub fn with_directory(mut self, collection: &str, pattern: &str, recursive: bool) -> Self {
+        self.directories.push((
+            String::from(collection),
+            DirectoryDescriptor::new(pattern, recursive),
+        ));
+        self
+    }
+
+    /// Builds a texture atlas from a keyed texture once it has loaded
+    pub fn with_atlas(
+        mut self,
+        key: AssetKey<TextureAtlas>,
+        texture: AssetKey<Texture>,
+        columns: usize,
+        rows: usize,
+    ) -> Self {
+        self.atlases.push((
+            String::from(key.name),
+            AtlasDescriptor {
+                texture: String::from(texture.name),
+                columns,
+                rows,
+            },
+        ));
+        self
+    }
+
+    /// Sets the number of times failed loads are retried
+    pub fn with_retries(mut self, max_retries: usize) -> Self {
+        self.retry_policy = RetryPolicy { max_retries };
+        self
+    }
+}
+
+/// returns loading data for the assets referenced by a data file which haven't been requested,
+/// reporting references to files which don't exist. Dangling references are still requested,
+/// so they fail to load and are replaced by a fallback if possible.
+fn referenced_assets(
+    source: &str,
+    references: Vec<AssetReference>,
+    pending: &[LoadingProgressData],
+    packs: &AssetPacks,
+    named_assets: &NamedAssets,
+    errors: &DataFileErrors,
+) -> Vec<LoadingProgressData> {
+    let mut assets: Vec<LoadingProgressData> = Vec::new();
+
+    for reference in references.into_iter() {
+        let is_requested = named_assets.contains(&reference.path)
+            || pending
+                .iter()
+                .chain(assets.iter())
+                .any(|asset| asset.path == reference.path);
+        if is_requested {
+            continue;
+        }
+
+        if packs.file_size(&reference.path).is_none() {
+            errors.push(DataFileError {
+                path: String::from(source),
+                line: 0,
+                column: 0,
+                message: format!("references {}, which does not exist", reference.path),
+            });
+        }
+
+        assets.push(LoadingProgressData::named(
+            &reference.path,
+            reference.kind,
+            &reference.path,
+        ));
+    }
+
+    assets
+}
+
+/// returns the file size in bytes, which is at least 1 so missing files still count
+fn file_size(packs: &AssetPacks, path: &str) -> u64 {
+    packs.file_size(path).unwrap_or(0).max(1)
 }
 
 fn asset_loading_system(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut textures: ResMut<Assets<Texture>>,
+    fallbacks: Res<FallbackAssets>,
+    mut packs: ResMut<AssetPacks>,
+    textures: Res<Assets<Texture>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut named_assets: ResMut<NamedAssets>,
+    mut collections: ResMut<AssetCollections>,
     mut loading_status: ResMut<LoadingStatus>,
+    mut group_loaded_events: ResMut<Events<AssetGroupLoaded>>,
+    mut failed_events: ResMut<Events<AssetLoadFailed>>,
+    dependencies: Res<DataDependencies>,
+    data_errors: Res<DataFileErrors>,
+    data_types: Res<DataFileTypes>,
     mut assets_to_load: Query<(Entity, &mut LoadAssets)>,
 ) {
+    let mut pending_paths = Vec::new();
+
     // trigger loading of new items
     for (entity, mut loader) in &mut assets_to_load.iter() {
+        let group_name = loader.group.clone();
+        let retry_policy = loader.retry_policy;
+        if !loading_status.groups.contains_key(&group_name) {
+            loading_status.groups.insert(
+                group_name.clone(),
+                GroupProgress {
+                    label: loader.label.clone(),
+                    ..Default::default()
+                },
+            );
+        }
+
+        // expand directories into the files they contain, so each file is tracked
+        if !loader.directories.is_empty() {
+            let directories = std::mem::take(&mut loader.directories);
+            for (collection, directory) in directories.iter() {
+                let files = directory.expand(collection, &packs);
+                if files.is_empty() {
+                    warn!(pattern = %directory.pattern, "No files found");
+                }
+
+                loader.assets.extend(files);
+            }
+        }
+
         // drain filter, but not experimental
         let mut i = 0;
         while i < loader.assets.len() {
             let tex = &mut loader.assets[i];
+            let mut error = None;
+
             if tex.handle.is_none() {
-                tex.handle = Some(asset_server.load_untyped(&tex.path).unwrap());
-                loading_status.items_to_load += 1;
-                i += 1;
-                continue;
-            }
+                // retries are already counted in the loading status
+                if tex.attempts == 0 {
+                    tex.size = file_size(&packs, &tex.path);
+                    loading_status.items_to_load += 1;
 
-            // check loading state
-            let load_state = asset_server.get_load_state_untyped(tex.handle.unwrap());
-            if load_state.is_none() {
-                i += 1;
-                continue;
+                    let group = loading_status.groups.get_mut(&group_name).unwrap();
+                    group.items_to_load += 1;
+                    group.bytes_to_load += tex.size;
+                    group.is_complete = false;
+                    loading_status.current_phase = Some(group_name.clone());
+                }
+
+                tex.attempts += 1;
+                // packed files are decoded in memory by `load_packed_assets`
+                let loaded = if packs.contains(&tex.path) {
+                    Ok(packs.load(&tex.path))
+                } else {
+                    asset_server
+                        .load_untyped(&tex.path)
+                        .map_err(|err| format!("{:?}", err))
+                };
+
+                match loaded {
+                    Ok(handle) => {
+                        tex.handle = Some(handle);
+
+                        // handles are valid while loading, so named assets can be used straight away
+                        match &tex.asset_type {
+                            LoaderAssetType::Named(kind, name) => {
+                                named_assets.insert_untyped(name, handle, kind.type_id())
+                            }
+                            LoaderAssetType::Collection(collection, key) => {
+                                collections.insert(collection, key, handle)
+                            }
+                            _ => {}
+                        }
+
+                        i += 1;
+                        continue;
+                    }
+                    Err(err) => error = Some(err),
+                }
             }
 
-            // if loaded, check if we need to assign an ID
-            tex.is_loaded = match load_state.unwrap() {
-                LoadState::Loaded(_) => {
-                    loading_status.items_loaded += 1;
-
-                    match tex.asset_type {
-                        LoaderAssetType::TextureWithId(id) => {
-                            let asset = textures
-                                .get(&Handle::from(tex.handle.unwrap()))
-                                .unwrap()
-                                .clone();
-                            textures.set(Handle::from_u128(id), asset);
+            if error.is_none() {
+                // check loading state
+                let handle = tex.handle.unwrap();
+                let load_state = packs
+                    .get_load_state(handle)
+                    .or_else(|| asset_server.get_load_state_untyped(handle));
+                match load_state {
+                    Some(LoadState::Loaded(_)) => {
+                        loading_status.items_loaded += 1;
+
+                        let group = loading_status.groups.get_mut(&group_name).unwrap();
+                        group.items_loaded += 1;
+                        group.bytes_loaded += tex.size;
+
+                        // data files from a manifest take the type of the loader which read them
+                        if let LoaderAssetType::Named(AssetKind::Data(None), name) = &tex.asset_type
+                        {
+                            if let Some(type_id) = data_types.get(&tex.path) {
+                                named_assets.insert_untyped(name, handle, Some(type_id));
+                            }
                         }
-                        _ => {}
-                    };
 
-                    // texture is loaded, remove it
-                    true
+                        // texture is loaded, remove it
+                        tex.is_loaded = true;
+                        let source = tex.path.clone();
+                        loader.assets.remove(i);
+
+                        // load the assets referenced by data files in the same group
+                        let references = dependencies.take(&source);
+                        if !references.is_empty() {
+                            let referenced = referenced_assets(
+                                &source,
+                                references,
+                                &loader.assets,
+                                &packs,
+                                &named_assets,
+                                &data_errors,
+                            );
+                            loader.assets.extend(referenced);
+                        }
+                        continue;
+                    }
+                    Some(LoadState::Failed(_)) => {
+                        error = Some(String::from("the asset loader failed to load the file"))
+                    }
+                    _ => {
+                        // texture is not loaded, check the next texture
+                        i += 1;
+                        continue;
+                    }
                 }
-                _ => false,
-            };
+            }
 
-            if tex.is_loaded {
-                loader.assets.remove(i);
-            } else {
-                // texture is not loaded, check the next texture
+            let error = error.unwrap();
+            if retry_policy.should_retry(tex.attempts) {
+                warn!(
+                    asset = %tex.path,
+                    attempt = tex.attempts,
+                    %error,
+                    "Failed to load asset, retrying"
+                );
+                tex.handle = None;
                 i += 1;
+                continue;
+            }
+
+            let used_fallback = apply_fallback(tex, &fallbacks, &mut named_assets);
+            if let LoaderAssetType::Collection(collection, key) = &tex.asset_type {
+                collections.remove(collection, key);
             }
+            error!(
+                asset = %tex.path,
+                attempts = tex.attempts,
+                used_fallback,
+                %error,
+                "Failed to load asset"
+            );
+
+            loading_status.items_failed += 1;
+            let group = loading_status.groups.get_mut(&group_name).unwrap();
+            group.items_failed += 1;
+            group.bytes_loaded += tex.size;
+            if used_fallback {
+                loading_status.items_using_fallback += 1;
+                group.items_using_fallback += 1;
+            }
+
+            failed_events.send(AssetLoadFailed {
+                path: tex.path.clone(),
+                group: group_name.clone(),
+                error,
+                used_fallback,
+            });
+
+            loader.assets.remove(i);
         }
 
-        if loader.assets.is_empty() {
-            println!("Despawning entity");
+        if !loader.atlases.is_empty() {
+            build_atlases(
+                &mut loader,
+                &mut named_assets,
+                &textures,
+                &mut texture_atlases,
+            );
+        }
+
+        pending_paths.extend(loader.assets.iter().map(|asset| asset.path.clone()));
+
+        if loader.assets.is_empty() && loader.atlases.is_empty() {
+            trace!(?entity, "Despawning finished load request");
             commands.despawn(entity);
+
+            // other requests may still be loading assets in the same group
+            let group = loading_status.groups.get_mut(&group_name).unwrap();
+            if !group.is_complete && group.items_finished() == group.items_to_load {
+                info!(group = %group_name, "Loading complete");
+                group.is_complete = true;
+                group_loaded_events.send(AssetGroupLoaded {
+                    group: group_name.clone(),
+                });
+
+                if loading_status.current_phase.as_ref() == Some(&group_name) {
+                    loading_status.current_phase = None;
+                }
+            }
         }
     }
 
-    // check if we are currently loading anything
-    if loading_status.items_to_load == loading_status.items_loaded {
-        if !loading_status.initial_load_done {
-            println!("Loading complete");
-            loading_status.initial_load_done = true;
+    // data files which are reloaded after their group has loaded may reference new assets
+    for (source, references) in dependencies.take_unclaimed(&pending_paths) {
+        let referenced = referenced_assets(
+            &source,
+            references,
+            &[],
+            &packs,
+            &named_assets,
+            &data_errors,
+        );
+
+        if !referenced.is_empty() {
+            commands.spawn((LoadAssets::from(referenced),));
         }
+    }
 
+    // check if we are currently loading anything
+    if !loading_status.is_loading() {
         return;
     }
 
-    println!(
-        "Loaded {} of {} items",
-        loading_status.items_loaded, loading_status.items_to_load
+    trace!(
+        loaded = loading_status.items_loaded,
+        total = loading_status.items_to_load,
+        "Loading assets"
     );
 }
+
+/// Reports data files which failed to parse, including files which were edited while the game is running
+fn data_file_error_system(errors: Res<DataFileErrors>, mut events: ResMut<Events<DataFileError>>) {
+    for error in errors.take() {
+        error!(
+            path = %error.path,
+            line = error.line,
+            column = error.column,
+            "Unable to parse data file: {}",
+            error.message
+        );
+        events.send(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(bytes_loaded: u64, bytes_to_load: u64) -> GroupProgress {
+        GroupProgress {
+            label: String::from("level 1"),
+            items_loaded: 1,
+            items_to_load: 2,
+            bytes_loaded,
+            bytes_to_load,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn weights_progress_by_file_size() {
+        // one small file of two has loaded, so progress follows the bytes rather than the items
+        let progress = group(100, 1000);
+        assert!((progress.progress() - 0.1).abs() < 0.001);
+        assert_eq!(progress.percentage(), 10);
+
+        assert_eq!(group(999, 1000).percentage(), 99);
+    }
+
+    #[test]
+    fn completed_groups_are_at_full_progress() {
+        assert_eq!(group(0, 0).percentage(), 0);
+
+        let complete = GroupProgress {
+            is_complete: true,
+            ..group(900, 1000)
+        };
+        assert_eq!(complete.percentage(), 100);
+        assert_eq!(
+            GroupProgress {
+                is_complete: true,
+                ..group(0, 0)
+            }
+            .percentage(),
+            100
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use bevy::{asset::HandleId, prelude::*};
+use serde::Deserialize;
+use std::{any::TypeId, collections::HashMap, fs, path::Path};
+use tracing::{error, warn};
+
+use crate::{
+    AssetKey, DirectoryDescriptor, LoadAssets, LoaderAssetType, LoadingProgressData, RetryPolicy,
+};
+
+/// The kinds of asset that can be declared in a manifest
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AssetKind {
+    Texture,
+    Font,
+    Audio,
+
+    /// A data file, with the type of the asset if it is known
+    Data(Option<TypeId>),
+}
+
+impl AssetKind {
+    /// returns the kind of the asset type `T`, any type other than a texture,
+    /// font or audio source is treated as a data file
+    pub fn of<T: 'static>() -> Self {
+        let type_id = TypeId::of::<T>();
+
+        if type_id == TypeId::of::<Texture>() {
+            AssetKind::Texture
+        } else if type_id == TypeId::of::<Font>() {
+            AssetKind::Font
+        } else if type_id == TypeId::of::<AudioSource>() {
+            AssetKind::Audio
+        } else {
+            AssetKind::Data(Some(type_id))
+        }
+    }
+
+    /// returns the type of the asset, or None for data files from a manifest
+    /// where the type is set by the loader registered for the file extension.
+    /// Their type is recorded in `DataFileTypes` when they've loaded.
+    pub fn type_id(self) -> Option<TypeId> {
+        match self {
+            AssetKind::Texture => Some(TypeId::of::<Texture>()),
+            AssetKind::Font => Some(TypeId::of::<Font>()),
+            AssetKind::Audio => Some(TypeId::of::<AudioSource>()),
+            AssetKind::Data(type_id) => type_id,
+        }
+    }
+}
+
+/// Describes a texture atlas which is built from a named texture in the same group
+#[derive(Clone, Debug, Deserialize)]
+pub struct AtlasDescriptor {
+    /// The name of the texture the atlas is built from
+    pub texture: String,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+/// A named set of assets which are loaded as a unit. Each map is from
+/// the asset name to its path (or description for atlases).
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AssetGroup {
+    /// A human readable name for the group, shown while loading. Defaults to the group name.
+    #[serde(default)]
+    pub label: Option<String>,
+
+    #[serde(default)]
+    pub textures: HashMap<String, String>,
+
+    #[serde(default)]
+    pub fonts: HashMap<String, String>,
+
+    #[serde(default)]
+    pub audio: HashMap<String, String>,
+
+    #[serde(default)]
+    pub data: HashMap<String, String>,
+
+    #[serde(default)]
+    pub atlases: HashMap<String, AtlasDescriptor>,
+
+    /// Directories or patterns loaded into `AssetCollections`, by collection name
+    #[serde(default)]
+    pub directories: HashMap<String, DirectoryDescriptor>,
+}
+
+/// A RON file describing named asset groups, for example:
+///
+/// ```ron
+/// (
+///     groups: {
+///         "core": (
+///             textures: { "walk_sheet": "assets/walk_sprite_sheet.png" },
+///             fonts: { "ui_font": "assets/fonts/teletactile.ttf" },
+///             atlases: { "walk_atlas": (texture: "walk_sheet", columns: 9, rows: 4) },
+///         ),
+///     },
+/// )
+/// ```
+///
+/// Add the manifest as a resource and spawn the `LoadAssets` returned
+/// by `load_group` to load a group. Loaded assets are retrieved by name
+/// from the `NamedAssets` resource.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AssetManifest {
+    pub groups: HashMap<String, AssetGroup>,
+}
+
+impl AssetManifest {
+    /// Reads and parses a manifest file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        AssetManifest::from_bytes(&fs::read(path)?)
+    }
+
+    /// Parses a manifest, e.g. one read from an asset pack
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+        Ok(ron::de::from_bytes::<AssetManifest>(bytes)?)
+    }
+
+    /// returns a `LoadAssets` which loads every asset in the named group,
+    /// or None if the group isn't in the manifest
+    pub fn load_group(&self, group_name: &str) -> Option<LoadAssets> {
+        let group = match self.groups.get(group_name) {
+            Some(group) => group,
+            None => {
+                warn!(group = group_name, "Unknown asset group");
+                return None;
+            }
+        };
+
+        let mut assets = Vec::new();
+        let kinds = [
+            (&group.textures, AssetKind::Texture),
+            (&group.fonts, AssetKind::Font),
+            (&group.audio, AssetKind::Audio),
+            (&group.data, AssetKind::Data(None)),
+        ];
+
+        for (entries, kind) in kinds.iter() {
+            for (name, path) in entries.iter() {
+                assets.push(LoadingProgressData::named(path, *kind, name));
+            }
+        }
+
+        Some(LoadAssets {
+            group: String::from(group_name),
+            label: group
+                .label
+                .clone()
+                .unwrap_or_else(|| String::from(group_name)),
+            assets,
+            directories: group
+                .directories
+                .iter()
+                .map(|(name, directory)| (name.clone(), directory.clone()))
+                .collect(),
+            retry_policy: RetryPolicy::default(),
+            atlases: group
+                .atlases
+                .iter()
+                .map(|(name, atlas)| (name.clone(), atlas.clone()))
+                .collect(),
+        })
+    }
+}
+
+/// A resource which stores the handles of named assets loaded from a manifest
+#[derive(Default)]
+pub struct NamedAssets {
+    handles: HashMap<String, (HandleId, Option<TypeId>)>,
+}
+
+impl NamedAssets {
+    /// Registers a typed handle under the given name, replacing any existing handle
+    pub fn insert<T: 'static>(&mut self, name: &str, handle: Handle<T>) {
+        self.handles
+            .insert(name.to_string(), (handle.id, Some(TypeId::of::<T>())));
+    }
+
+    /// Registers an untyped handle, if `type_id` is None the handle can be retrieved as any type
+    pub fn insert_untyped(&mut self, name: &str, handle: HandleId, type_id: Option<TypeId>) {
+        self.handles.insert(name.to_string(), (handle, type_id));
+    }
+
+    /// returns the handle registered under the key, if it exists and has the key's type
+    pub fn get<T: 'static>(&self, key: AssetKey<T>) -> Option<Handle<T>> {
+        self.get_by_name(key.name)
+    }
+
+    /// returns the handle with the given name, if it exists and has the requested type
+    pub fn get_by_name<T: 'static>(&self, name: &str) -> Option<Handle<T>> {
+        let (handle, type_id) = self.handles.get(name)?;
+
+        match type_id {
+            Some(type_id) if *type_id != TypeId::of::<T>() => {
+                warn!(asset = name, "Asset was requested with the wrong type");
+                None
+            }
+            _ => Some(Handle::from(*handle)),
+        }
+    }
+
+    /// Removes the handle with the given name
+    pub fn remove(&mut self, name: &str) {
+        self.handles.remove(name);
+    }
+
+    /// returns true if an asset with the given name has been registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.handles.contains_key(name)
+    }
+}
+
+/// Builds any atlases in the loader whose texture has finished loading
+pub(crate) fn build_atlases(
+    loader: &mut LoadAssets,
+    named_assets: &mut NamedAssets,
+    textures: &Assets<Texture>,
+    texture_atlases: &mut Assets<TextureAtlas>,
+) {
+    let pending = std::mem::take(&mut loader.atlases);
+
+    for (name, atlas) in pending.into_iter() {
+        let is_waiting = loader.assets.iter().any(|asset| match &asset.asset_type {
+            LoaderAssetType::Named(AssetKind::Texture, texture) => *texture == atlas.texture,
+            _ => false,
+        });
+
+        let texture_handle = match named_assets.get_by_name::<Texture>(&atlas.texture) {
+            Some(handle) if !is_waiting => handle,
+            Some(_) => {
+                loader.atlases.push((name, atlas));
+                continue;
+            }
+            None => {
+                error!(
+                    atlas = %name,
+                    texture = %atlas.texture,
+                    "Unable to build atlas, the texture is not loaded"
+                );
+                continue;
+            }
+        };
+
+        let texture = match textures.get(&texture_handle) {
+            Some(texture) => texture,
+            None => {
+                loader.atlases.push((name, atlas));
+                continue;
+            }
+        };
+
+        let texture_atlas =
+            TextureAtlas::from_grid(texture_handle, texture.size, atlas.columns, atlas.rows);
+        named_assets.insert(&name, texture_atlases.add(texture_atlas));
+    }
+}
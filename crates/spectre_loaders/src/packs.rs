@@ -0,0 +1,589 @@
+use anyhow::anyhow;
+use bevy::{
+    asset::{AssetLoader, HandleId, LoadState},
+    prelude::*,
+};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tracing::{info, warn};
+
+use crate::wildcard_match;
+
+/// The bytes every pack file starts with
+const PACK_MAGIC: &[u8; 4] = b"SPAK";
+
+/// The location of a file inside a pack
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct PackEntry {
+    /// The offset of the data from the end of the index
+    offset: u64,
+
+    /// The size of the data stored in the pack
+    stored_size: u64,
+
+    /// The size of the file once decompressed
+    size: u64,
+    compressed: bool,
+}
+
+/// An indexed archive of asset files. Files are stored under the path they
+/// are loaded with, e.g. `assets/fonts/teletactile.ttf`.
+///
+/// A pack is the `SPAK` magic bytes, the length of the index as a little endian
+/// u64, the bincode encoded index and then the file data.
+pub struct AssetPack {
+    path: PathBuf,
+    entries: HashMap<String, PackEntry>,
+    data_start: u64,
+}
+
+impl AssetPack {
+    /// Opens a pack and reads its index
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let mut file = File::open(path.as_ref())?;
+        let file_size = file.metadata()?.len();
+
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic)?;
+        if &magic != PACK_MAGIC {
+            return Err(anyhow!("{:?} is not an asset pack", path.as_ref()));
+        }
+
+        let mut index_size = [0u8; 8];
+        file.read_exact(&mut index_size)?;
+        let index_size = u64::from_le_bytes(index_size);
+
+        // check the size before allocating, so a damaged header can't allocate any amount
+        if index_size > file_size.saturating_sub(12) {
+            return Err(invalid_data(format!(
+                "{:?} has an index of {} bytes, which is larger than the pack",
+                path.as_ref(),
+                index_size
+            )));
+        }
+
+        let mut index = vec![0u8; index_size as usize];
+        file.read_exact(&mut index)?;
+
+        Ok(AssetPack {
+            path: path.as_ref().to_path_buf(),
+            entries: bincode::deserialize(index.as_slice())?,
+            data_start: 12 + index_size,
+        })
+    }
+
+    /// returns true if the pack contains a file with the given asset path
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(&normalise_path(path))
+    }
+
+    /// returns the decompressed size of the file, if the pack contains it
+    pub fn file_size(&self, path: &str) -> Option<u64> {
+        self.entries
+            .get(&normalise_path(path))
+            .map(|entry| entry.size)
+    }
+
+    /// returns the asset paths of every file in the pack
+    pub fn files(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+
+    /// Reads and decompresses a file from the pack
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, anyhow::Error> {
+        let entry = self
+            .entries
+            .get(&normalise_path(path))
+            .ok_or_else(|| anyhow!("{} is not in pack {:?}", path, self.path))?;
+
+        let mut file = File::open(&self.path)?;
+        let end = self
+            .data_start
+            .checked_add(entry.offset)
+            .and_then(|start| start.checked_add(entry.stored_size));
+        match end {
+            Some(end) if end <= file.metadata()?.len() => {}
+            _ => {
+                return Err(invalid_data(format!(
+                    "{} extends past the end of pack {:?}",
+                    path, self.path
+                )))
+            }
+        }
+
+        file.seek(SeekFrom::Start(self.data_start + entry.offset))?;
+        let mut stored = vec![0u8; entry.stored_size as usize];
+        file.read_exact(&mut stored)?;
+
+        if !entry.compressed {
+            return Ok(stored);
+        }
+
+        // the decompressed size isn't trusted for the allocation either, only checked afterwards
+        let mut bytes = Vec::new();
+        DeflateDecoder::new(stored.as_slice()).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != entry.size {
+            return Err(invalid_data(format!(
+                "{} in pack {:?} is {} bytes, expected {}",
+                path,
+                self.path,
+                bytes.len(),
+                entry.size
+            )));
+        }
+
+        Ok(bytes)
+    }
+}
+
+/// Builds a pack file from loose asset files
+#[derive(Default)]
+pub struct PackWriter {
+    compress: bool,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl PackWriter {
+    /// Creates a writer, if `compress` is true files are stored with deflate compression
+    /// unless compressing them doesn't make them smaller
+    pub fn new(compress: bool) -> Self {
+        PackWriter {
+            compress,
+            files: Vec::new(),
+        }
+    }
+
+    /// Adds a file which is loaded with the given asset path
+    pub fn add_file(&mut self, path: &str, bytes: Vec<u8>) {
+        self.files.push((normalise_path(path), bytes));
+    }
+
+    /// Adds every file in the directory and its subdirectories, stored under their paths
+    /// including the directory, e.g. `assets/fonts/teletactile.ttf`.
+    /// Returns the number of files added.
+    pub fn add_directory<P: AsRef<Path>>(&mut self, directory: P) -> Result<usize, anyhow::Error> {
+        let mut count = 0;
+
+        // read_dir returns entries in any order, so sort them to always write the same pack
+        let mut paths = fs::read_dir(directory.as_ref())?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()?;
+        paths.sort();
+
+        for path in paths.into_iter() {
+            if path.is_dir() {
+                count += self.add_directory(&path)?;
+                continue;
+            }
+
+            self.add_file(&path.to_string_lossy(), fs::read(&path)?);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Writes the pack file
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), anyhow::Error> {
+        // the index is read back as a HashMap, but is written in path order so packs are reproducible
+        let mut entries = BTreeMap::new();
+        let mut data = Vec::new();
+
+        for (asset_path, bytes) in self.files.iter() {
+            let mut stored = None;
+            if self.compress {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(bytes)?;
+                let compressed = encoder.finish()?;
+
+                if compressed.len() < bytes.len() {
+                    stored = Some(compressed);
+                }
+            }
+
+            let compressed = stored.is_some();
+            let stored = stored.unwrap_or_else(|| bytes.clone());
+
+            entries.insert(
+                asset_path.clone(),
+                PackEntry {
+                    offset: data.len() as u64,
+                    stored_size: stored.len() as u64,
+                    size: bytes.len() as u64,
+                    compressed,
+                },
+            );
+            data.extend(stored);
+        }
+
+        let index = bincode::serialize(&entries)?;
+
+        let mut file = File::create(path)?;
+        file.write_all(PACK_MAGIC)?;
+        file.write_all(&(index.len() as u64).to_le_bytes())?;
+        file.write_all(&index)?;
+        file.write_all(&data)?;
+        Ok(())
+    }
+}
+
+/// A resource holding the packs assets are loaded from. Packs are layered, so a file in a
+/// pack which was added later (e.g. a mod) overrides the same file in the base pack, and files
+/// in any pack override loose files. Packed files are decoded in memory by the loaders
+/// registered with `add_packed_asset_loader`, as bevy's asset server only loads from disk.
+#[derive(Default)]
+pub struct AssetPacks {
+    packs: Vec<AssetPack>,
+
+    /// Packed files waiting to be decoded by `load_packed_assets`
+    queued: Vec<(HandleId, String)>,
+    load_states: HashMap<HandleId, LoadState>,
+}
+
+impl AssetPacks {
+    /// Adds a pack on top of the existing packs. Packs which can't be opened are skipped.
+    pub fn with_pack<P: AsRef<Path>>(mut self, path: P) -> Self {
+        match AssetPack::open(path.as_ref()) {
+            Ok(pack) => {
+                info!(pack = ?path.as_ref(), "Using asset pack");
+                self.packs.push(pack);
+            }
+            Err(err) => warn!(pack = ?path.as_ref(), %err, "Unable to open asset pack"),
+        }
+        self
+    }
+
+    /// Adds every `.pak` file in the directory on top of the existing packs, in name order,
+    /// so mods can override files in the base pack. A missing directory is ignored.
+    pub fn with_mods<P: AsRef<Path>>(self, directory: P) -> Self {
+        let mut paths: Vec<PathBuf> = match fs::read_dir(directory.as_ref()) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().map(|ext| ext == "pak").unwrap_or(false))
+                .collect(),
+            Err(_) => return self,
+        };
+        paths.sort();
+
+        paths.iter().fold(self, |packs, path| packs.with_pack(path))
+    }
+
+    /// returns the topmost pack layer which contains the file
+    fn find(&self, path: &str) -> Option<(usize, &AssetPack)> {
+        self.packs
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, pack)| pack.contains(path))
+    }
+
+    /// returns true if any pack contains the file
+    pub fn contains(&self, path: &str) -> bool {
+        self.find(path).is_some()
+    }
+
+    /// returns the size of a file, from the topmost pack which contains it or from disk
+    pub fn file_size(&self, path: &str) -> Option<u64> {
+        match self.find(path) {
+            Some((_, pack)) => pack.file_size(path),
+            None => fs::metadata(path).map(|metadata| metadata.len()).ok(),
+        }
+    }
+
+    /// returns the asset paths of every packed file in the directory, including
+    /// subdirectories if `recursive` is true, which match the file name pattern
+    pub fn find_files(&self, directory: &Path, file_pattern: &str, recursive: bool) -> Vec<String> {
+        let directory = normalise_path(&directory.to_string_lossy());
+        let prefix = if directory.is_empty() {
+            directory
+        } else {
+            format!("{}/", directory)
+        };
+
+        let mut files: Vec<String> = self
+            .packs
+            .iter()
+            .flat_map(|pack| pack.files())
+            .filter(|file| file.starts_with(&prefix))
+            .filter(|file| recursive || !file[prefix.len()..].contains('/'))
+            .filter(|file| {
+                let file_name = file.rsplit('/').next().unwrap_or_default();
+                wildcard_match(file_pattern, file_name)
+            })
+            .cloned()
+            .collect();
+
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// returns the bytes of a file, from the topmost pack which contains it or from disk
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, anyhow::Error> {
+        match self.find(path) {
+            Some((_, pack)) => pack.read(path),
+            None => Ok(fs::read(path)?),
+        }
+    }
+
+    /// Queues a packed file to be decoded, returning the handle it is loaded into.
+    /// Files which aren't in a pack should be loaded with the asset server instead.
+    pub fn load(&mut self, path: &str) -> HandleId {
+        let handle = HandleId::new();
+        self.queued.push((handle, String::from(path)));
+        self.load_states.insert(handle, LoadState::Loading(0));
+        handle
+    }
+
+    /// returns the load state of a handle returned by `load`, or None for other handles
+    pub fn get_load_state(&self, handle: HandleId) -> Option<LoadState> {
+        self.load_states.get(&handle).cloned()
+    }
+}
+
+/// Decodes packed files into the assets of one type
+trait PackedAssetLoader: Send + Sync {
+    fn load(
+        &self,
+        handle: HandleId,
+        path: &Path,
+        bytes: Vec<u8>,
+        resources: &Resources,
+    ) -> Result<(), anyhow::Error>;
+}
+
+struct TypedPackedAssetLoader<TAsset, TLoader> {
+    loader: TLoader,
+    marker: PhantomData<fn() -> TAsset>,
+}
+
+impl<TAsset, TLoader> PackedAssetLoader for TypedPackedAssetLoader<TAsset, TLoader>
+where
+    TAsset: Send + Sync + 'static,
+    TLoader: AssetLoader<TAsset>,
+{
+    fn load(
+        &self,
+        handle: HandleId,
+        path: &Path,
+        bytes: Vec<u8>,
+        resources: &Resources,
+    ) -> Result<(), anyhow::Error> {
+        let asset = self.loader.from_bytes(path, bytes)?;
+        let mut assets = resources
+            .get_mut::<Assets<TAsset>>()
+            .ok_or_else(|| anyhow!("the asset type of {:?} hasn't been added", path))?;
+
+        assets.set(Handle::from_id(handle), asset);
+        Ok(())
+    }
+}
+
+/// A resource holding the loaders used for packed files, by file extension
+#[derive(Default)]
+pub struct PackedAssetLoaders {
+    loaders: HashMap<String, Arc<dyn PackedAssetLoader>>,
+}
+
+/// Adds loaders which decode packed files, see `AssetPacks`
+pub trait AddPackedAssetLoader {
+    /// Uses the loader for packed files with its extensions. Add the same loader the
+    /// asset server uses for the asset type, so packed and loose files load the same way.
+    fn add_packed_asset_loader<TAsset, TLoader>(&mut self, loader: TLoader) -> &mut Self
+    where
+        TAsset: Send + Sync + 'static,
+        TLoader: AssetLoader<TAsset>;
+}
+
+impl AddPackedAssetLoader for AppBuilder {
+    fn add_packed_asset_loader<TAsset, TLoader>(&mut self, loader: TLoader) -> &mut Self
+    where
+        TAsset: Send + Sync + 'static,
+        TLoader: AssetLoader<TAsset>,
+    {
+        if self.resources().get::<PackedAssetLoaders>().is_none() {
+            self.init_resource::<PackedAssetLoaders>();
+        }
+
+        let extensions: Vec<String> = loader
+            .extensions()
+            .iter()
+            .map(|extension| String::from(*extension))
+            .collect();
+        let loader: Arc<dyn PackedAssetLoader> = Arc::new(TypedPackedAssetLoader {
+            loader,
+            marker: PhantomData::<fn() -> TAsset>,
+        });
+
+        {
+            let mut packed_loaders = self.resources().get_mut::<PackedAssetLoaders>().unwrap();
+            for extension in extensions.into_iter() {
+                packed_loaders.loaders.insert(extension, loader.clone());
+            }
+        }
+        self
+    }
+}
+
+/// Decodes the packed files queued by `AssetPacks::load` with their registered loader
+pub(crate) fn load_packed_assets(_world: &mut World, resources: &mut Resources) {
+    let queued = match resources.get_mut::<AssetPacks>() {
+        Some(mut packs) => std::mem::take(&mut packs.queued),
+        None => return,
+    };
+    if queued.is_empty() {
+        return;
+    }
+
+    for (handle, path) in queued.into_iter() {
+        let result = load_packed_asset(handle, &path, resources);
+        if let Err(err) = &result {
+            warn!(asset = %path, %err, "Unable to load packed asset");
+        }
+
+        let state = match result {
+            Ok(()) => LoadState::Loaded(0),
+            Err(_) => LoadState::Failed(0),
+        };
+        if let Some(mut packs) = resources.get_mut::<AssetPacks>() {
+            packs.load_states.insert(handle, state);
+        }
+    }
+}
+
+fn load_packed_asset(
+    handle: HandleId,
+    path: &str,
+    resources: &Resources,
+) -> Result<(), anyhow::Error> {
+    let extension = Path::new(path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let loader = resources
+        .get::<PackedAssetLoaders>()
+        .and_then(|loaders| loaders.loaders.get(&extension).cloned())
+        .ok_or_else(|| {
+            anyhow!(
+                "no packed asset loader is registered for .{} files",
+                extension
+            )
+        })?;
+
+    let bytes = resources
+        .get::<AssetPacks>()
+        .ok_or_else(|| anyhow!("there are no asset packs"))?
+        .read(path)?;
+
+    loader.load(handle, Path::new(path), bytes, resources)
+}
+
+fn invalid_data(message: String) -> anyhow::Error {
+    io::Error::new(ErrorKind::InvalidData, message).into()
+}
+
+/// Uses forward slashes and removes any leading `./`, so paths match on every platform
+fn normalise_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    path.trim_start_matches("./").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_directory::TestDirectory;
+
+    fn is_invalid_data(err: anyhow::Error) -> bool {
+        err.downcast_ref::<io::Error>()
+            .map(|err| err.kind() == ErrorKind::InvalidData)
+            .unwrap_or(false)
+    }
+
+    #[test]
+    fn reads_packed_files() {
+        let directory = TestDirectory::new("spectre_pack_test");
+        let pack_path = directory.path().join("test.pak");
+
+        let mut writer = PackWriter::new(true);
+        writer.add_file("assets/data/sword.item", vec![7u8; 256]);
+        writer.add_file("assets/data/weapons/axe.item", vec![1, 2, 3]);
+        writer.write(&pack_path).unwrap();
+
+        let pack = AssetPack::open(&pack_path).unwrap();
+        assert_eq!(pack.read("assets/data/sword.item").unwrap(), vec![7u8; 256]);
+        assert_eq!(
+            pack.read("./assets/data/weapons/axe.item").unwrap(),
+            vec![1, 2, 3]
+        );
+        assert!(!pack.contains("assets/data/shield.item"));
+
+        let packs = AssetPacks::default().with_pack(&pack_path);
+        assert_eq!(
+            packs.find_files(Path::new("assets/data"), "*.item", false),
+            vec![String::from("assets/data/sword.item")]
+        );
+        assert_eq!(
+            packs
+                .find_files(Path::new("assets/data"), "*.item", true)
+                .len(),
+            2
+        );
+        assert_eq!(
+            packs.read("assets/data/weapons/axe.item").unwrap(),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn writes_the_same_pack_for_the_same_files() {
+        let test_directory = TestDirectory::new("spectre_pack_order_test");
+        let directory = test_directory.path();
+        fs::create_dir_all(directory.join("assets/data")).unwrap();
+        for name in ["a", "b", "c", "d"].iter() {
+            fs::write(directory.join("assets/data").join(name), name.as_bytes()).unwrap();
+        }
+
+        let pack_bytes = |name: &str| {
+            let mut writer = PackWriter::new(true);
+            writer.add_directory(directory.join("assets")).unwrap();
+            writer.write(directory.join(name)).unwrap();
+            fs::read(directory.join(name)).unwrap()
+        };
+
+        assert_eq!(pack_bytes("first.pak"), pack_bytes("second.pak"));
+    }
+
+    #[test]
+    fn rejects_sizes_larger_than_the_pack() {
+        let directory = TestDirectory::new("spectre_pack_size_test");
+        let pack_path = directory.path().join("test.pak");
+
+        let mut writer = PackWriter::new(false);
+        writer.add_file("assets/data/sword.item", vec![7u8; 256]);
+        writer.write(&pack_path).unwrap();
+
+        // the end of the file data is lost
+        let pack = AssetPack::open(&pack_path).unwrap();
+        let file = fs::OpenOptions::new().write(true).open(&pack_path).unwrap();
+        file.set_len(pack.data_start + 128).unwrap();
+        assert!(is_invalid_data(
+            pack.read("assets/data/sword.item").unwrap_err()
+        ));
+
+        // the index size is damaged
+        let mut bytes = fs::read(&pack_path).unwrap();
+        bytes[4..12].copy_from_slice(&u64::MAX.to_le_bytes());
+        fs::write(&pack_path, bytes).unwrap();
+        assert!(is_invalid_data(AssetPack::open(&pack_path).err().unwrap()));
+    }
+}
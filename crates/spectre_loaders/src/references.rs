@@ -0,0 +1,161 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    cell::RefCell,
+    fmt,
+    marker::PhantomData,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::{AssetKind, NamedAssets};
+
+/// A reference from a data file to another asset, written in the file as the asset's path,
+/// e.g. `icon: "assets/icons/fireball.png"`. Referenced assets are loaded with the data file
+/// and registered in `NamedAssets` under their path.
+pub struct AssetRef<T> {
+    pub path: String,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: 'static> AssetRef<T> {
+    pub fn new(path: &str) -> Self {
+        AssetRef {
+            path: String::from(path),
+            marker: PhantomData,
+        }
+    }
+
+    /// returns the handle of the referenced asset, once loading has been requested
+    pub fn handle(&self, named_assets: &NamedAssets) -> Option<Handle<T>> {
+        named_assets.get_by_name(&self.path)
+    }
+}
+
+impl<T> Clone for AssetRef<T> {
+    fn clone(&self) -> Self {
+        AssetRef {
+            path: self.path.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for AssetRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AssetRef({})", self.path)
+    }
+}
+
+impl<T> Serialize for AssetRef<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.path.serialize(serializer)
+    }
+}
+
+impl<'de, T: 'static> Deserialize<'de> for AssetRef<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let path = String::deserialize(deserializer)?;
+
+        COLLECTED_REFERENCES.with(|references| {
+            if let Some(references) = references.borrow_mut().as_mut() {
+                references.push(AssetReference {
+                    path: path.clone(),
+                    kind: AssetKind::of::<T>(),
+                });
+            }
+        });
+
+        Ok(AssetRef {
+            path,
+            marker: PhantomData,
+        })
+    }
+}
+
+/// An asset referenced by a data file
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssetReference {
+    pub path: String,
+    pub kind: AssetKind,
+}
+
+thread_local! {
+    static COLLECTED_REFERENCES: RefCell<Option<Vec<AssetReference>>> = RefCell::new(None);
+}
+
+/// Runs the function, returning its result and every `AssetRef` deserialized while it ran
+pub fn collect_references<R, F: FnOnce() -> R>(f: F) -> (R, Vec<AssetReference>) {
+    let previous = COLLECTED_REFERENCES.with(|references| references.replace(Some(Vec::new())));
+    let result = f();
+    let collected = COLLECTED_REFERENCES.with(|references| references.replace(previous));
+
+    (result, collected.unwrap_or_default())
+}
+
+/// Collects the assets referenced by loaded data files, which are loaded by the
+/// `asset_loading_system` in the same group as the file which references them
+#[derive(Clone, Default)]
+pub struct DataDependencies {
+    dependencies: Arc<Mutex<Vec<(String, Vec<AssetReference>)>>>,
+}
+
+impl DataDependencies {
+    pub fn push(&self, path: &Path, references: Vec<AssetReference>) {
+        self.dependencies
+            .lock()
+            .unwrap()
+            .push((path.to_string_lossy().to_string(), references));
+    }
+
+    /// Removes and returns the references from the data file loaded from the given path.
+    /// The asset server may report a longer path than was requested, so paths are matched
+    /// by their ending.
+    pub fn take(&self, path: &str) -> Vec<AssetReference> {
+        let mut dependencies = self.dependencies.lock().unwrap();
+
+        match dependencies
+            .iter()
+            .position(|(loaded, _)| Path::new(loaded).ends_with(path))
+        {
+            Some(index) => dependencies.remove(index).1,
+            None => Vec::new(),
+        }
+    }
+
+    /// Removes and returns the references from data files which aren't in the pending paths
+    pub fn take_unclaimed(&self, pending: &[String]) -> Vec<(String, Vec<AssetReference>)> {
+        let mut dependencies = self.dependencies.lock().unwrap();
+        let (claimed, unclaimed): (Vec<_>, Vec<_>) = std::mem::take(&mut *dependencies)
+            .into_iter()
+            .partition(|(loaded, _)| pending.iter().any(|path| Path::new(loaded).ends_with(path)));
+
+        *dependencies = claimed;
+        unclaimed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Item {
+        icon: AssetRef<Texture>,
+    }
+
+    #[test]
+    fn collects_references() {
+        let (item, references) =
+            collect_references(|| ron::de::from_str::<Item>(r#"(icon: "assets/sword.png")"#));
+
+        assert_eq!(item.unwrap().icon.path, "assets/sword.png");
+        assert_eq!(
+            references,
+            vec![AssetReference {
+                path: String::from("assets/sword.png"),
+                kind: AssetKind::Texture,
+            }]
+        );
+    }
+}
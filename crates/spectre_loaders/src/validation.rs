@@ -0,0 +1,448 @@
+use ron::Value;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+use crate::{
+    collect_references,
+    data_loaders::{DataFileError, DataFormat},
+};
+
+/// The name of the field holding a data file's format version.
+/// Files without a version field are treated as version 1.
+pub const VERSION_FIELD: &str = "version";
+
+/// Upgrades a data file from `from_version` to the next version
+#[derive(Clone, Copy)]
+pub struct Migration {
+    pub from_version: u32,
+    pub migrate: fn(&mut Value) -> Result<(), String>,
+}
+
+/// A type which is loaded from data files, with an optional format version,
+/// migrations for older files and validation of the loaded values
+pub trait DataAsset: DeserializeOwned + Send + Sync + 'static {
+    /// The current format version of the data files
+    const VERSION: u32 = 1;
+
+    /// The migrations used to upgrade files written for older versions
+    fn migrations() -> Vec<Migration> {
+        Vec::new()
+    }
+
+    /// Checks the loaded values, reporting problems to the validator
+    fn validate(&self, _validator: &mut Validator) {}
+}
+
+/// Collects the problems found while validating a data file
+#[derive(Default)]
+pub struct Validator {
+    pub errors: Vec<String>,
+}
+
+impl Validator {
+    /// Reports the message if the condition is false
+    pub fn check(&mut self, condition: bool, message: &str) {
+        if !condition {
+            self.errors.push(String::from(message));
+        }
+    }
+
+    /// Reports the field if its value is outside the inclusive range
+    pub fn check_range<T: PartialOrd + Display>(&mut self, field: &str, value: T, min: T, max: T) {
+        if value < min || value > max {
+            self.errors.push(format!(
+                "{} is {}, expected a value from {} to {}",
+                field, value, min, max
+            ));
+        }
+    }
+}
+
+/// returns the named field of a struct or map value
+pub fn field<'a>(value: &'a Value, name: &str) -> Option<&'a Value> {
+    match value {
+        Value::Map(map) => map
+            .iter()
+            .find(|(key, _)| **key == Value::String(String::from(name)))
+            .map(|(_, value)| value),
+        _ => None,
+    }
+}
+
+fn file_version(value: &Value) -> Result<u32, String> {
+    match field(value, VERSION_FIELD) {
+        None => Ok(1),
+        Some(Value::Number(number)) => number
+            .as_i64()
+            .filter(|version| *version > 0)
+            .map(|version| version as u32)
+            .ok_or_else(|| String::from("version must be a positive integer")),
+        Some(_) => Err(String::from("version must be a positive integer")),
+    }
+}
+
+/// Parses, migrates and validates a data file
+pub fn load_data_file<TAsset: DataAsset>(
+    path: &Path,
+    bytes: &[u8],
+    format: DataFormat,
+) -> Result<TAsset, DataFileError> {
+    let error = |message: String| DataFileError {
+        path: path.to_string_lossy().to_string(),
+        line: 0,
+        column: 0,
+        message,
+    };
+
+    // binary files can't be read without their type, so can't be migrated
+    let mut value = if format.is_self_describing() {
+        // syntax errors are reported with their location here
+        Some(format.parse::<Value>(path, bytes)?)
+    } else {
+        None
+    };
+
+    let version = match &value {
+        Some(value) => file_version(value).map_err(error)?,
+        None => TAsset::VERSION,
+    };
+
+    let asset = if version == TAsset::VERSION {
+        // parse the file directly so type errors are reported with their location
+        format.parse::<TAsset>(path, bytes)?
+    } else if version > TAsset::VERSION {
+        return Err(error(format!(
+            "version {} is newer than the supported version {}",
+            version,
+            TAsset::VERSION
+        )));
+    } else {
+        let mut value = value.take().unwrap();
+        let migrations = TAsset::migrations();
+        for from_version in version..TAsset::VERSION {
+            let migration = migrations
+                .iter()
+                .find(|migration| migration.from_version == from_version)
+                .ok_or_else(|| error(format!("no migration from version {}", from_version)))?;
+
+            (migration.migrate)(&mut value).map_err(|err| {
+                error(format!(
+                    "migration from version {} failed: {}",
+                    from_version, err
+                ))
+            })?;
+        }
+
+        value
+            .into_rust::<TAsset>()
+            .map_err(|err| error(format!("{} after migrating", err.code)))?
+    };
+
+    let mut validator = Validator::default();
+    asset.validate(&mut validator);
+    if !validator.errors.is_empty() {
+        return Err(error(validator.errors.join("; ")));
+    }
+
+    Ok(asset)
+}
+
+type Validate = fn(&Path, &[u8], DataFormat) -> Result<(), DataFileError>;
+type Convert = fn(&Path, &[u8], DataFormat) -> Result<Vec<u8>, DataFileError>;
+
+/// Maps data file extensions to their types and formats, so data files can be
+/// validated and converted to binary without running the game
+#[derive(Default)]
+pub struct DataFileRegistry {
+    validators: HashMap<String, (DataFormat, Validate)>,
+
+    /// (binary extension, converter) by source extension
+    converters: HashMap<String, (String, Convert)>,
+}
+
+impl DataFileRegistry {
+    /// Validates files with the given extensions as RON `TAsset`s
+    pub fn register<TAsset: DataAsset>(self, extensions: &[&str]) -> Self {
+        extensions.iter().fold(self, |registry, extension| {
+            registry.register_format::<TAsset>(extension, DataFormat::Ron)
+        })
+    }
+
+    /// Validates files with the given extension as `TAsset`s in the given format
+    pub fn register_format<TAsset: DataAsset>(
+        mut self,
+        extension: &str,
+        format: DataFormat,
+    ) -> Self {
+        self.validators.insert(
+            String::from(extension),
+            (format, |path, bytes, format| {
+                load_data_file::<TAsset>(path, bytes, format).map(|_| ())
+            }),
+        );
+        self
+    }
+
+    /// Converts files with the source extension to binary files with the binary extension
+    /// when `convert_directory` is called. The source extension must be registered first.
+    pub fn register_binary<TAsset: DataAsset + Serialize>(
+        mut self,
+        source_extension: &str,
+        binary_extension: &str,
+    ) -> Self {
+        self.converters.insert(
+            String::from(source_extension),
+            (String::from(binary_extension), |path, bytes, format| {
+                let asset = load_data_file::<TAsset>(path, bytes, format)?;
+                DataFormat::Bincode
+                    .serialize(&asset)
+                    .map_err(|err| DataFileError {
+                        path: path.to_string_lossy().to_string(),
+                        line: 0,
+                        column: 0,
+                        message: err.to_string(),
+                    })
+            }),
+        );
+        self.register_format::<TAsset>(binary_extension, DataFormat::Bincode)
+    }
+
+    /// Converts every data file in the source directory which has a binary extension registered,
+    /// writing the binary files to the same relative path in the output directory.
+    /// Returns the number of files converted and any errors.
+    pub fn convert_directory<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        source: P,
+        output: Q,
+    ) -> (usize, Vec<DataFileError>) {
+        let source = source.as_ref();
+        let mut converted = 0;
+        let mut errors = Vec::new();
+
+        for path in data_files(source).iter() {
+            let extension = extension(path);
+            let (binary_extension, convert) = match self.converters.get(&extension) {
+                Some(converter) => converter,
+                None => continue,
+            };
+            let format = match self.validators.get(&extension) {
+                Some((format, _)) => *format,
+                None => {
+                    errors.push(DataFileError {
+                        path: path.to_string_lossy().to_string(),
+                        line: 0,
+                        column: 0,
+                        message: format!(
+                            "no format is registered for .{} files, so they can't be converted",
+                            extension
+                        ),
+                    });
+                    continue;
+                }
+            };
+
+            let relative = path.strip_prefix(source).unwrap_or(path);
+            let output_path = output
+                .as_ref()
+                .join(relative)
+                .with_extension(binary_extension);
+
+            let result = read_file(path)
+                .and_then(|bytes| convert(path, bytes.as_slice(), format))
+                .and_then(|bytes| {
+                    output_path
+                        .parent()
+                        .map(fs::create_dir_all)
+                        .unwrap_or(Ok(()))
+                        .and_then(|_| fs::write(&output_path, bytes))
+                        .map_err(|err| file_error(&output_path, err))
+                });
+
+            match result {
+                Ok(_) => converted += 1,
+                Err(err) => errors.push(err),
+            }
+        }
+
+        (converted, errors)
+    }
+
+    /// Validates every registered data file in the directory and its subdirectories,
+    /// including that the files they reference exist, returning the number of files
+    /// checked and any errors
+    pub fn validate_directory<P: AsRef<Path>>(&self, directory: P) -> (usize, Vec<DataFileError>) {
+        let mut checked = 0;
+        let mut errors = Vec::new();
+
+        for path in data_files(directory.as_ref()).iter() {
+            let (format, validate) = match self.validators.get(&extension(path)) {
+                Some(validator) => validator,
+                None => continue,
+            };
+
+            checked += 1;
+            let (result, references) = collect_references(|| {
+                read_file(path).and_then(|bytes| validate(path, bytes.as_slice(), *format))
+            });
+
+            if let Err(err) = result {
+                errors.push(err);
+            }
+
+            for reference in references.iter() {
+                if !Path::new(&reference.path).is_file() {
+                    errors.push(DataFileError {
+                        path: path.to_string_lossy().to_string(),
+                        line: 0,
+                        column: 0,
+                        message: format!("references {}, which does not exist", reference.path),
+                    });
+                }
+            }
+        }
+
+        (checked, errors)
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn file_error(path: &Path, err: std::io::Error) -> DataFileError {
+    DataFileError {
+        path: path.to_string_lossy().to_string(),
+        line: 0,
+        column: 0,
+        message: err.to_string(),
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, DataFileError> {
+    fs::read(path).map_err(|err| file_error(path, err))
+}
+
+/// returns every file in the directory and its subdirectories, sorted by path
+fn data_files(directory: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    find_data_files(directory, &mut files);
+    files.sort();
+    files
+}
+
+fn find_data_files(directory: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(err) => {
+            warn!(?directory, %err, "Unable to read directory");
+            return;
+        }
+    };
+
+    for path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        if path.is_dir() {
+            find_data_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_directory::TestDirectory;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Serialize)]
+    struct Item {
+        damage: i32,
+    }
+
+    fn rename_power(value: &mut Value) -> Result<(), String> {
+        if let Value::Map(map) = value {
+            let power = map
+                .remove(&Value::String(String::from("power")))
+                .ok_or_else(|| String::from("missing power"))?;
+            map.insert(Value::String(String::from("damage")), power);
+        }
+        Ok(())
+    }
+
+    impl DataAsset for Item {
+        const VERSION: u32 = 2;
+
+        fn migrations() -> Vec<Migration> {
+            vec![Migration {
+                from_version: 1,
+                migrate: rename_power,
+            }]
+        }
+
+        fn validate(&self, validator: &mut Validator) {
+            validator.check_range("damage", self.damage, 0, 100);
+        }
+    }
+
+    #[test]
+    fn migrates_and_validates() {
+        let path = Path::new("sword.item");
+        let ron = DataFormat::Ron;
+
+        let item = load_data_file::<Item>(path, b"(power: 10)", ron).unwrap();
+        assert_eq!(item.damage, 10);
+
+        let item = load_data_file::<Item>(path, b"(version: 2, damage: 20)", ron).unwrap();
+        assert_eq!(item.damage, 20);
+
+        assert!(load_data_file::<Item>(path, b"(version: 2, damage: 200)", ron).is_err());
+        assert!(load_data_file::<Item>(path, b"(version: 3, damage: 20)", ron).is_err());
+    }
+
+    #[test]
+    fn loads_other_formats() {
+        let path = Path::new("sword.item");
+
+        let item = load_data_file::<Item>(path, br#"{"power": 10}"#, DataFormat::Json).unwrap();
+        assert_eq!(item.damage, 10);
+
+        let item =
+            load_data_file::<Item>(path, b"version = 2\ndamage = 20", DataFormat::Toml).unwrap();
+        assert_eq!(item.damage, 20);
+
+        let bytes = DataFormat::Bincode.serialize(&Item { damage: 30 }).unwrap();
+        let item = load_data_file::<Item>(path, bytes.as_slice(), DataFormat::Bincode).unwrap();
+        assert_eq!(item.damage, 30);
+    }
+
+    #[test]
+    fn reports_converters_without_a_format() {
+        let directory = TestDirectory::new("spectre_unregistered_convert");
+        let source = directory.path();
+        fs::write(source.join("sword.item"), b"(version: 2, damage: 20)").unwrap();
+
+        // the binary converter is registered directly, without a format for .item files
+        let mut registry = DataFileRegistry::default();
+        registry.converters.insert(
+            String::from("item"),
+            (String::from("itemb"), |_, bytes, _| Ok(bytes.to_vec())),
+        );
+
+        let (converted, errors) = registry.convert_directory(source, source.join("out"));
+
+        assert_eq!(converted, 0);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].path.ends_with("sword.item"));
+    }
+}
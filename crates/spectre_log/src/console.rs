@@ -0,0 +1,161 @@
+use bevy::prelude::*;
+use tracing::Level;
+
+use crate::LogBuffer;
+
+/// A resource controlling the in game log console, which shows the most recent log lines
+/// over the game. The console is spawned once a font has been set.
+pub struct LogConsole {
+    pub visible: bool,
+    pub max_lines: usize,
+    pub toggle_key: KeyCode,
+    pub font: Option<Handle<Font>>,
+    buffer: LogBuffer,
+}
+
+impl LogConsole {
+    pub fn new(buffer: LogBuffer) -> Self {
+        LogConsole {
+            visible: false,
+            max_lines: 12,
+            toggle_key: KeyCode::Grave,
+            font: None,
+            buffer,
+        }
+    }
+}
+
+/// Shows the log lines recorded in the buffer returned by `init_logging` in an
+/// overlay, which is toggled with the backtick key
+pub struct LogConsolePlugin {
+    buffer: LogBuffer,
+}
+
+impl LogConsolePlugin {
+    pub fn new(buffer: LogBuffer) -> Self {
+        LogConsolePlugin { buffer }
+    }
+}
+
+impl Plugin for LogConsolePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(LogConsole::new(self.buffer.clone()))
+            .add_system(toggle_log_console.system())
+            .add_system(update_log_console.system());
+    }
+}
+
+/// The root UI node of the console
+pub struct LogConsoleRoot {
+    material: Handle<ColorMaterial>,
+}
+
+/// A line of text in the console
+pub struct LogConsoleLine {
+    index: usize,
+}
+
+fn toggle_log_console(input: Res<Input<KeyCode>>, mut console: ResMut<LogConsole>) {
+    if input.just_pressed(console.toggle_key) {
+        console.visible = !console.visible;
+    }
+}
+
+fn level_color(level: Level) -> Color {
+    match level {
+        Level::ERROR => Color::rgb(1., 0.3, 0.3),
+        Level::WARN => Color::rgb(1., 0.8, 0.3),
+        Level::INFO => Color::WHITE,
+        _ => Color::rgb(0.6, 0.6, 0.6),
+    }
+}
+
+fn update_log_console(
+    mut commands: Commands,
+    console: Res<LogConsole>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut roots: Query<&LogConsoleRoot>,
+    mut lines: Query<(&LogConsoleLine, &mut Text, &mut Draw)>,
+) {
+    let font = match console.font {
+        Some(font) => font,
+        None => return,
+    };
+
+    let mut has_console = false;
+    for root in &mut roots.iter() {
+        has_console = true;
+        if let Some(material) = materials.get_mut(&root.material) {
+            material.color.a = if console.visible { 0.75 } else { 0. };
+        }
+    }
+
+    if !has_console {
+        spawn_log_console(&mut commands, &mut materials, font, console.max_lines);
+        return;
+    }
+
+    let recent = console.buffer.recent(console.max_lines);
+    for (line, mut text, mut draw) in &mut lines.iter() {
+        draw.is_visible = console.visible;
+        if !console.visible {
+            continue;
+        }
+
+        match recent.get(line.index) {
+            Some(log_line) => {
+                text.value = log_line.to_string();
+                text.style.color = level_color(log_line.level);
+            }
+            None => text.value.clear(),
+        }
+    }
+}
+
+fn spawn_log_console(
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    font: Handle<Font>,
+    max_lines: usize,
+) {
+    let material = materials.add(Color::rgba(0., 0., 0., 0.).into());
+    commands
+        .spawn(NodeComponents {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.),
+                    top: Val::Px(0.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.), Val::Auto),
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(4.)),
+                ..Default::default()
+            },
+            material,
+            ..Default::default()
+        })
+        .with(LogConsoleRoot { material })
+        .with_children(|parent| {
+            for index in 0..max_lines {
+                parent
+                    .spawn(TextComponents {
+                        text: Text {
+                            value: String::new(),
+                            font,
+                            style: TextStyle {
+                                font_size: 14.0,
+                                color: Color::WHITE,
+                            },
+                        },
+                        draw: Draw {
+                            is_visible: false,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .with(LogConsoleLine { index });
+            }
+        });
+}
@@ -0,0 +1,212 @@
+use std::{
+    collections::VecDeque,
+    fmt::{self, Write},
+    sync::{Arc, Mutex},
+};
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{
+    fmt as fmt_layer,
+    layer::{Context, Layer, SubscriberExt},
+    util::SubscriberInitExt,
+    EnvFilter,
+};
+
+mod console;
+
+pub use console::*;
+
+pub mod prelude {
+    pub use crate::*;
+    pub use tracing::{debug, error, info, trace, warn};
+}
+
+/// The filter used when `RUST_LOG` isn't set, which hides the per frame logs
+pub const DEFAULT_LOG_FILTER: &str = "info";
+
+/// A log event recorded by a `LogBuffer`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogLine {
+    pub level: Level,
+
+    /// The module which logged the event, e.g. `spectre_loaders::packs`
+    pub target: String,
+    pub message: String,
+
+    /// The structured fields of the event, e.g. `("scene", "Menu")`
+    pub fields: Vec<(String, String)>,
+}
+
+impl fmt::Display for LogLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:5} {}: {}", self.level, self.target, self.message)?;
+        for (name, value) in self.fields.iter() {
+            write!(f, " {}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A shared buffer holding the most recent log events, which is filled by the subscriber
+/// layer returned by `layer` and read by the `LogConsolePlugin` or by tests
+#[derive(Clone)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<LogLine>>>,
+    capacity: usize,
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        LogBuffer::new(200)
+    }
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        LogBuffer {
+            lines: Arc::new(Mutex::new(VecDeque::new())),
+            capacity,
+        }
+    }
+
+    /// returns a subscriber layer which records events in this buffer
+    pub fn layer(&self) -> LogBufferLayer {
+        LogBufferLayer {
+            buffer: self.clone(),
+        }
+    }
+
+    fn push(&self, line: LogLine) {
+        let mut lines = self.lines.lock().unwrap();
+        lines.push_back(line);
+        while lines.len() > self.capacity {
+            lines.pop_front();
+        }
+    }
+
+    /// returns the most recent lines, oldest first
+    pub fn recent(&self, count: usize) -> Vec<LogLine> {
+        let lines = self.lines.lock().unwrap();
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(count))
+            .cloned()
+            .collect()
+    }
+
+    /// Removes and returns every line in the buffer
+    pub fn take(&self) -> Vec<LogLine> {
+        self.lines.lock().unwrap().drain(..).collect()
+    }
+}
+
+/// A subscriber layer which records events in a `LogBuffer`
+pub struct LogBufferLayer {
+    buffer: LogBuffer,
+}
+
+impl<S: Subscriber> Layer<S> for LogBufferLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+
+        let metadata = event.metadata();
+        self.buffer.push(LogLine {
+            level: *metadata.level(),
+            target: String::from(metadata.target()),
+            message: visitor.message,
+            fields: visitor.fields,
+        });
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            self.fields
+                .push((String::from(field.name()), String::from(value)));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            self.fields
+                .push((String::from(field.name()), format!("{:?}", value)));
+        }
+    }
+}
+
+/// returns the filter set in `RUST_LOG`, or the default filter. Filters are a level or a
+/// list of per crate levels, e.g. `info,spectre_loaders=debug,spectre_state=trace`.
+pub fn log_filter(default_filter: &str) -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_filter))
+}
+
+/// Writes log events to stdout and records them in the returned buffer, which can be
+/// shown in game with the `LogConsolePlugin`. Call this once, before the app is built.
+pub fn init_logging(default_filter: &str) -> LogBuffer {
+    let buffer = LogBuffer::default();
+
+    let result = tracing_subscriber::registry()
+        .with(log_filter(default_filter))
+        .with(fmt_layer::layer())
+        .with(buffer.layer())
+        .try_init();
+    if let Err(err) = result {
+        eprintln!("Unable to set up logging: {}", err);
+    }
+
+    buffer
+}
+
+/// Runs the function, returning the events logged while it ran which pass the filter,
+/// e.g. `capture_logs("spectre_state=debug", || ...)` in tests
+pub fn capture_logs<F: FnOnce()>(filter: &str, f: F) -> Vec<LogLine> {
+    let buffer = LogBuffer::new(usize::MAX);
+    let subscriber = tracing_subscriber::registry()
+        .with(EnvFilter::new(filter))
+        .with(buffer.layer());
+
+    tracing::subscriber::with_default(subscriber, f);
+    buffer.take()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::{debug, info};
+
+    #[test]
+    fn captures_filtered_logs() {
+        let lines = capture_logs("info,spectre_log=debug,noisy=warn", || {
+            debug!(asset = "assets/fonts/ui.ttf", "Loaded asset");
+            info!(target: "noisy", "Loaded 1 of 2 items");
+        });
+
+        assert_eq!(
+            lines,
+            vec![LogLine {
+                level: Level::DEBUG,
+                target: String::from("spectre_log::tests"),
+                message: String::from("Loaded asset"),
+                fields: vec![(String::from("asset"), String::from("assets/fonts/ui.ttf"))],
+            }]
+        );
+        assert_eq!(
+            lines[0].to_string(),
+            "DEBUG spectre_log::tests: Loaded asset asset=assets/fonts/ui.ttf"
+        );
+    }
+}
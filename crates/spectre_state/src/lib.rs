@@ -1,33 +1,293 @@
 /// Inspired by https://github.com/Bobox214/Kataster/tree/master/src (MIT License)
 use core::fmt::Debug;
+use std::collections::{HashSet, VecDeque};
+use tracing::{debug, info, trace, warn};
 
+mod scene;
+mod sub_state;
+mod transition;
+
+pub use scene::*;
+pub use sub_state::*;
+pub use transition::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum GameStatus {
     Idle,
     Entering,
     Exiting,
     Running,
+
+    /// The scene is in the stack below the current scene. It keeps its entities, and only its
+    /// systems for the Paused status run, see `Scene::on_pause`.
+    Paused,
+}
+
+/// A change to the scene stack, carried out over the next state updates
+#[derive(Clone, Copy, Debug)]
+pub enum SceneChange<TScene> {
+    /// Exits the current scene and enters the given scene in its place
+    Replace(TScene),
+
+    /// Pauses the current scene and enters the given scene on top of it
+    Push(TScene),
+
+    /// Exits the current scene and resumes the scene below it
+    Pop,
 }
 
-/// A resource which should be added to the world with a custom scene enum
-pub struct GameState<TScene: Clone + Copy + Debug> {
+/// A callback which can veto a scene change, returning the reason it isn't allowed,
+/// e.g. "can't leave while saving". It is given the current scene and the change.
+pub type TransitionGuard<TScene> =
+    Box<dyn Fn(Option<&TScene>, &SceneChange<TScene>) -> Result<(), String> + Send + Sync>;
+
+/// The number of transition events kept in the history by default
+pub const DEFAULT_HISTORY_LIMIT: usize = 32;
+
+/// The number of events buffered for `take_events`, independent of the history limit. The
+/// `SceneTransitionPlugin` takes them every frame, so this is only reached without the plugin.
+pub const PENDING_EVENT_LIMIT: usize = 256;
+
+/// A resource which should be added to the world with a custom scene enum.
+/// The current scene is the top of a stack of scenes, the scenes below it are paused.
+pub struct GameState<TScene: Clone + Debug> {
     pub status: GameStatus,
     pub current: Option<TScene>,
-    pub next: Option<TScene>,
+
+    /// The queued changes and their transitions, which are carried out in order
+    pending: VecDeque<(SceneChange<TScene>, SceneTransition)>,
+
+    /// The paused scenes below the current scene, from the bottom of the stack
+    pub stack: Vec<TScene>,
+
+    /// The transition used by `set_transition`, `push` and `pop`
+    pub transition: SceneTransition,
+
+    /// True while a sub state's parent scene isn't running, see `SubStatePlugin`.
+    /// Scene systems don't run while the state is suspended.
+    pub suspended: bool,
+
+    /// The reasons the current scene can't finish exiting, see `set_hold`
+    holds: HashSet<String>,
+
+    /// The most recent transition events, oldest first, for debugging
+    pub history: VecDeque<SceneTransitionEvent<TScene>>,
+    pub history_limit: usize,
+
+    guards: Vec<TransitionGuard<TScene>>,
+
+    /// The events which haven't been sent yet, see `take_events`
+    events: Vec<SceneTransitionEvent<TScene>>,
+
+    /// The scenes the current change is from and to
+    change_from: Option<TScene>,
+    change_to: Option<TScene>,
+
+    /// The transition of the current exit or enter phase
+    active: SceneTransition,
+    phase: PhaseTimer,
+}
+
+/// The time spent in the current exit or enter phase
+#[derive(Clone, Copy, Debug, Default)]
+struct PhaseTimer {
+    elapsed: f32,
+    frames: u32,
+
+    /// The frame on which the transition effect finished
+    finished_frame: Option<u32>,
 }
 
-impl<TScene: Clone + Copy + Debug> GameState<TScene> {
-    /// Set the current transition, to be carried out in the next state update
+impl PhaseTimer {
+    /// Starts a phase, which finishes immediately if `finished` is true
+    fn start(finished: bool) -> Self {
+        PhaseTimer {
+            elapsed: 0.,
+            frames: 0,
+            finished_frame: if finished { Some(0) } else { None },
+        }
+    }
+}
+
+impl<TScene: Clone + Debug> Default for GameState<TScene> {
+    fn default() -> Self {
+        GameState {
+            status: GameStatus::Idle,
+            current: None,
+            pending: VecDeque::new(),
+            stack: Vec::new(),
+            transition: SceneTransition::default(),
+            suspended: false,
+            holds: HashSet::new(),
+            history: VecDeque::new(),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            guards: Vec::new(),
+            events: Vec::new(),
+            change_from: None,
+            change_to: None,
+            active: SceneTransition::default(),
+            phase: PhaseTimer::default(),
+        }
+    }
+}
+
+impl<TScene: Clone + Debug> GameState<TScene> {
+    /// Sets the transition used for scene changes
+    pub fn with_transition(mut self, transition: SceneTransition) -> Self {
+        self.transition = transition;
+        self
+    }
+
+    /// Adds a guard which can veto scene changes
+    pub fn with_guard<F>(mut self, guard: F) -> Self
+    where
+        F: Fn(Option<&TScene>, &SceneChange<TScene>) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.add_guard(guard);
+        self
+    }
+
+    /// Sets the number of transition events kept in the history
+    pub fn with_history_limit(mut self, history_limit: usize) -> Self {
+        self.history_limit = history_limit;
+        self
+    }
+
+    /// Adds a guard which can veto scene changes. Guards are checked when a queued change
+    /// is about to start, and a vetoed change is dropped from the queue.
+    pub fn add_guard<F>(&mut self, guard: F)
+    where
+        F: Fn(Option<&TScene>, &SceneChange<TScene>) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.guards.push(Box::new(guard));
+    }
+
+    /// Queue a transition, to be carried out once the changes queued before it are done.
+    /// The current scene is replaced by the next scene.
     pub fn set_transition(&mut self, next: TScene) {
-        self.next = Some(next);
+        self.change_scene(SceneChange::Replace(next), self.transition);
     }
 
-    /// returns true if the current scene is defined and matches the given scene
-    pub fn is_in_scene(&self, scene: &TScene) -> bool {
-        if self.current.is_none() {
-            return false;
+    /// Pause the current scene and enter the given scene on top of it, e.g. a pause menu
+    pub fn push(&mut self, scene: TScene) {
+        self.change_scene(SceneChange::Push(scene), self.transition);
+    }
+
+    /// Exit the current scene and resume the scene below it
+    pub fn pop(&mut self) {
+        self.change_scene(SceneChange::Pop, self.transition);
+    }
+
+    /// Queue a change to be carried out using the given transition
+    pub fn change_scene(&mut self, change: SceneChange<TScene>, transition: SceneTransition) {
+        self.pending.push_back((change, transition));
+    }
+
+    /// Holds or releases the current scene's exit for the given reason. A scene which is held
+    /// doesn't finish exiting, keeping the transition effect covering the screen, e.g. until
+    /// the assets for the next scene are loaded.
+    pub fn set_hold(&mut self, reason: &str, held: bool) {
+        if held {
+            self.holds.insert(String::from(reason));
+        } else {
+            self.holds.remove(reason);
+        }
+    }
+
+    /// Cancels the current scene's exit and drops the change which started it, so the scene
+    /// keeps running. The cancelled change is recorded as `Vetoed` with the given reason.
+    pub fn abort_exit(&mut self, reason: &str) {
+        if self.status != GameStatus::Exiting {
+            return;
+        }
+
+        warn!(scene = ?self.current, %reason, "Scene exit aborted");
+        self.pending.pop_front();
+        self.status = GameStatus::Running;
+        self.phase = PhaseTimer::default();
+        self.record(TransitionPhase::Vetoed(String::from(reason)));
+    }
+
+    /// returns true if anything is holding the current scene's exit
+    pub fn is_held(&self) -> bool {
+        !self.holds.is_empty()
+    }
+
+    /// returns the scene which will be current after the next queued change, if there is one
+    pub fn next_scene(&self) -> Option<TScene> {
+        self.pending
+            .front()
+            .and_then(|(change, _)| self.scene_after(change))
+    }
+
+    fn scene_after(&self, change: &SceneChange<TScene>) -> Option<TScene> {
+        match change {
+            SceneChange::Replace(next) | SceneChange::Push(next) => Some(next.clone()),
+            SceneChange::Pop => self.stack.last().cloned(),
         }
+    }
 
-        std::mem::discriminant(&self.current.unwrap()) == std::mem::discriminant(scene)
+    /// Removes and returns the transition events since the last call, which are
+    /// sent as bevy events by the `SceneTransitionPlugin`
+    pub fn take_events(&mut self) -> Vec<SceneTransitionEvent<TScene>> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn record(&mut self, phase: TransitionPhase) {
+        let event = SceneTransitionEvent {
+            from: self.change_from.clone(),
+            to: self.change_to.clone(),
+            phase,
+        };
+
+        self.history.push_back(event.clone());
+        self.events.push(event);
+        while self.history.len() > self.history_limit {
+            self.history.pop_front();
+        }
+
+        // stop the buffer growing forever if nothing takes the events
+        if self.events.len() > PENDING_EVENT_LIMIT {
+            self.events.remove(0);
+        }
+    }
+
+    /// returns the next queued change which the guards allow, dropping vetoed changes
+    fn next_allowed_change(&mut self) -> Option<(SceneChange<TScene>, SceneTransition)> {
+        while let Some((change, transition)) = self.pending.front().cloned() {
+            let veto = self
+                .guards
+                .iter()
+                .find_map(|guard| guard(self.current.as_ref(), &change).err());
+
+            match veto {
+                None => return Some((change, transition)),
+                Some(reason) => {
+                    info!(?change, scene = ?self.current, %reason, "Scene change vetoed");
+
+                    self.pending.pop_front();
+                    self.change_from = self.current.clone();
+                    self.change_to = self.scene_after(&change);
+                    self.record(TransitionPhase::Vetoed(reason));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// returns the current scene, including its payload for scenes like `Game { level: 3 }`
+    pub fn current_scene(&self) -> Option<&TScene> {
+        self.current.as_ref()
+    }
+
+    /// returns true if the current scene is defined and is the same variant as the given scene.
+    /// Payloads aren't compared, so `Game { level: 1 }` matches `Game { level: 3 }`.
+    pub fn is_in_scene(&self, scene: &TScene) -> bool {
+        match &self.current {
+            Some(current) => std::mem::discriminant(current) == std::mem::discriminant(scene),
+            None => false,
+        }
     }
 
     /// returns true if the current status matches the given scene
@@ -35,69 +295,241 @@ impl<TScene: Clone + Copy + Debug> GameState<TScene> {
         std::mem::discriminant(&self.status) == std::mem::discriminant(status)
     }
 
-    /// Update the state, moving from Idle >> Entering >> Running or
-    /// Running >> Exiting >> Entering >> Running one frame at a time
+    /// returns true if the scene is the current scene or paused in the stack
+    pub fn is_in_stack(&self, scene: &TScene) -> bool {
+        self.scene_status(scene).is_some()
+    }
+
+    /// returns the status of the given scene, which is `Paused` for scenes
+    /// below the current scene, or None if the scene isn't in the stack
+    pub fn scene_status(&self, scene: &TScene) -> Option<GameStatus> {
+        if self.is_in_scene(scene) {
+            return Some(self.status);
+        }
+
+        let discriminant = std::mem::discriminant(scene);
+        self.stack
+            .iter()
+            .find(|paused| std::mem::discriminant(*paused) == discriminant)
+            .map(|_| GameStatus::Paused)
+    }
+
+    /// returns the number of scenes in the stack, including the current scene
+    pub fn depth(&self) -> usize {
+        self.stack.len() + if self.current.is_some() { 1 } else { 0 }
+    }
+
+    /// returns the transition of the current exit or enter phase
+    pub fn active_transition(&self) -> &SceneTransition {
+        &self.active
+    }
+
+    /// returns the progress of the current exit or enter phase from 0 to 1,
+    /// which is 1 when no transition is running
+    pub fn transition_progress(&self) -> f32 {
+        match self.status {
+            GameStatus::Entering | GameStatus::Exiting if self.active.duration > 0. => {
+                (self.phase.elapsed / self.active.duration).min(1.)
+            }
+            _ => 1.,
+        }
+    }
+
+    /// returns how much of the screen the transition effect covers, from 0 to 1.
+    /// This rises to 1 as a scene exits and falls back to 0 as the next scene enters.
+    pub fn transition_coverage(&self) -> f32 {
+        match self.status {
+            GameStatus::Exiting => self.transition_progress(),
+            GameStatus::Entering => 1. - self.transition_progress(),
+            _ => 0.,
+        }
+    }
+
+    /// returns true if scene systems for the given scene and status should run this frame.
+    /// Systems for the Entering status only run on its first frame, and systems for the
+    /// Exiting status only run on the frame the exit transition finishes. Systems for the
+    /// Paused status run while the scene is in the stack below the current scene.
+    pub fn should_run(&self, scene: &TScene, status: &GameStatus) -> bool {
+        let is_phase_frame = match status {
+            GameStatus::Entering => self.is_phase_start(),
+            GameStatus::Exiting => self.is_transition_finishing(),
+            _ => true,
+        };
+
+        !self.suspended && self.scene_status(scene).as_ref() == Some(status) && is_phase_frame
+    }
+
+    /// returns true on the first frame of the current status, when enter systems run
+    pub fn is_phase_start(&self) -> bool {
+        self.phase.frames == 0
+    }
+
+    /// returns true on the frame the transition effect of the current phase finished,
+    /// when exit systems run
+    pub fn is_transition_finishing(&self) -> bool {
+        self.phase.finished_frame == Some(self.phase.frames)
+    }
+
+    /// Update the state without advancing transitions, which is enough for
+    /// instant transitions. See `advance`.
     pub fn update(&mut self) {
+        self.advance(0.);
+    }
+
+    /// Update the state after the given number of seconds, moving from Idle >> Entering >> Running
+    /// or Running >> Exiting >> Entering >> Running. Pushed scenes move from Running >> Entering,
+    /// pausing the current scene, and popped scenes move from Running >> Exiting >> Running,
+    /// resuming the scene below.
+    ///
+    /// The Exiting and Entering phases last until their transition effect has finished, then move
+    /// on in the next update, so instant transitions take a frame per phase. Exiting scenes
+    /// don't finish while they are held, see `set_hold`.
+    pub fn advance(&mut self, delta_seconds: f32) {
+        if let GameStatus::Entering | GameStatus::Exiting = self.status {
+            let was_finished = self.phase.finished_frame.is_some();
+            self.phase.elapsed += delta_seconds;
+            self.phase.frames += 1;
+
+            if !was_finished {
+                let is_held = self.status == GameStatus::Exiting && self.is_held();
+                if self.phase.elapsed >= self.active.duration && !is_held {
+                    self.phase.finished_frame = Some(self.phase.frames);
+                }
+                return;
+            }
+        }
+
+        let status = self.status;
+        self.step();
+
+        if self.status != status {
+            let is_held = self.status == GameStatus::Exiting && self.is_held();
+            self.phase = PhaseTimer::start(self.active.duration <= 0. && !is_held);
+            self.record(match self.status {
+                GameStatus::Exiting => TransitionPhase::Exiting,
+                GameStatus::Entering => TransitionPhase::Entering,
+                _ => TransitionPhase::Finished,
+            });
+        }
+    }
+
+    /// Records the scenes a change which is starting is from and to
+    fn start_change(&mut self, change: &SceneChange<TScene>, transition: SceneTransition) {
+        self.change_from = self.current.clone();
+        self.change_to = self.scene_after(change);
+        self.active = transition;
+    }
+
+    fn step(&mut self) {
         match &self.status {
-            GameStatus::Idle => {
-                match self.next {
-                    Some(next_state) => {
-                        println!("[Transition] IDLE to ENTERED::{:?}", next_state);
-                        self.status = GameStatus::Entering;
-                        self.current = Some(next_state);
-                    }
-                    None => {
-                        println!("[Transition] IDLE to ? ignored as no next state");
+            GameStatus::Idle => match self.next_allowed_change() {
+                Some((change, transition)) => {
+                    self.pending.pop_front();
+
+                    self.start_change(&change, transition);
+                    match change {
+                        SceneChange::Replace(next_state) | SceneChange::Push(next_state) => {
+                            debug!(to = ?next_state, "Idle to entering");
+                            self.status = GameStatus::Entering;
+                            self.current = Some(next_state);
+                        }
+                        SceneChange::Pop => {
+                            warn!("Can't pop a scene, there are no scenes");
+                            self.record(TransitionPhase::Vetoed(String::from(
+                                "there are no scenes to pop",
+                            )));
+                        }
                     }
-                };
-            }
+                }
+                None => {
+                    trace!("Idle with no next scene");
+                }
+            },
             GameStatus::Entering => {
-                println!(
-                    "[Transition] ENTERED::{:?} to RUNNING::{:?}",
-                    self.current, self.current
-                );
+                debug!(scene = ?self.current, "Entering to running");
                 self.status = GameStatus::Running;
             }
-            GameStatus::Exiting => match self.next {
-                Some(_) => {
-                    match self.current {
-                        None => println!(
-                            "[Transition] EXITING::NoState to ENTERED::{:?}",
-                            self.next.unwrap()
-                        ),
-                        Some(_) => println!(
-                            "[Transition] EXITING::{:?} to ENTERED::{:?}",
-                            self.current.unwrap(),
-                            self.next.unwrap()
-                        ),
+            // the change which started the exit is still at the front of the queue
+            GameStatus::Exiting => {
+                if let Some((SceneChange::Replace(_), _)) = self.pending.front() {
+                    if let Some(paused) = self.stack.pop() {
+                        self.exit_paused_scene(paused);
+                        return;
                     }
+                }
+
+                self.finish_exit();
+            }
+            GameStatus::Running => match self.next_allowed_change() {
+                None => {} // no transition queued
+                Some((SceneChange::Push(next_state), transition)) => {
+                    debug!(from = ?self.current, to = ?next_state, "Running to paused, pushing scene");
 
+                    self.pending.pop_front();
+                    self.start_change(&SceneChange::Push(next_state.clone()), transition);
+                    if let Some(current) = self.current.take() {
+                        self.stack.push(current);
+                    }
+                    self.current = Some(next_state);
                     self.status = GameStatus::Entering;
-                    self.current = self.next.clone();
-                    self.next = None;
                 }
-                _ => {
-                    println!("[Transition] Can't move from EXITING::{:?} to ENTERING::None, no next state defined", self.current.unwrap());
+                Some((change, transition)) => {
+                    debug!(scene = ?self.current, ?change, "Running to exiting");
+                    self.start_change(&change, transition);
+                    self.status = GameStatus::Exiting;
                 }
             },
-            GameStatus::Running => match self.next {
-                None => {} // no transition queued
-                Some(_) => {
-                    match self.current {
-                        None => println!(
-                            "[Transition] RUNNING::NoState, EXITING::{:?}",
-                            self.next.unwrap()
-                        ),
-                        Some(_) => println!(
-                            "[Transition] RUNNING::{:?} to EXITING::{:?}, next state is {:?}",
-                            self.current.unwrap(),
-                            self.current.unwrap(),
-                            self.next.unwrap(),
-                        ),
-                    }
-                    self.status = GameStatus::Exiting;
+            GameStatus::Paused => {
+                // only scenes in the stack are paused, the current scene never is
+                warn!(scene = ?self.current, "The current scene can't be paused");
+                self.status = GameStatus::Running;
+            }
+        }
+    }
+
+    /// Exits a scene from the stack below the scene which just exited, so a replace leaves no
+    /// paused scenes behind. The scene is current for the frame its exit systems run.
+    fn exit_paused_scene(&mut self, paused: TScene) {
+        debug!(from = ?self.current, to = ?paused, "Exiting paused scene");
+
+        self.current = Some(paused);
+        self.change_from = self.current.clone();
+
+        // the transition already covers the screen, so the exit finishes straight away
+        self.phase = PhaseTimer {
+            elapsed: self.active.duration,
+            frames: 0,
+            finished_frame: Some(0),
+        };
+        self.record(TransitionPhase::Exiting);
+    }
+
+    /// Carries out the change at the front of the queue once the current scene has exited
+    fn finish_exit(&mut self) {
+        match self.pending.pop_front() {
+            // a push which starts an exit replaces the exiting scene
+            Some((SceneChange::Replace(next_state), _))
+            | Some((SceneChange::Push(next_state), _)) => {
+                debug!(from = ?self.current, to = ?next_state, "Exiting to entering");
+
+                self.status = GameStatus::Entering;
+                self.current = Some(next_state);
+            }
+            Some((SceneChange::Pop, _)) => match self.stack.pop() {
+                Some(resumed) => {
+                    debug!(from = ?self.current, to = ?resumed, "Exiting to resumed");
+                    self.status = GameStatus::Running;
+                    self.current = Some(resumed);
+                }
+                None => {
+                    debug!(from = ?self.current, "Exiting to idle");
+                    self.status = GameStatus::Idle;
+                    self.current = None;
                 }
             },
+            None => {
+                warn!(scene = ?self.current, "Can't finish exiting, no next scene is queued");
+            }
         }
     }
 }
@@ -105,21 +537,20 @@ impl<TScene: Clone + Copy + Debug> GameState<TScene> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bevy::prelude::Color;
 
-    #[derive(Clone, Copy, Debug)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
     #[allow(dead_code)]
     pub enum TestStates {
         A,
         B,
+        C,
     }
 
     #[test]
     fn transitions_on_update() {
-        let mut gs = GameState::<TestStates> {
-            status: GameStatus::Idle,
-            current: None,
-            next: Some(TestStates::A),
-        };
+        let mut gs = GameState::<TestStates>::default();
+        gs.set_transition(TestStates::A);
 
         match gs.status {
             GameStatus::Idle => assert!(true),
@@ -136,4 +567,214 @@ mod tests {
             _ => assert!(false),
         };
     }
+
+    #[test]
+    fn pushes_and_pops_scenes() {
+        let mut gs = GameState::<TestStates>::default();
+        gs.set_transition(TestStates::A);
+        gs.update();
+        gs.update();
+
+        gs.push(TestStates::B);
+        gs.update();
+        assert!(gs.is_in_scene(&TestStates::B));
+        assert_eq!(gs.status, GameStatus::Entering);
+        assert_eq!(gs.scene_status(&TestStates::A), Some(GameStatus::Paused));
+        assert_eq!(gs.depth(), 2);
+
+        gs.update();
+        gs.pop();
+        gs.update();
+        assert_eq!(gs.scene_status(&TestStates::B), Some(GameStatus::Exiting));
+
+        gs.update();
+        assert!(gs.is_in_scene(&TestStates::A));
+        assert_eq!(gs.status, GameStatus::Running);
+        assert!(!gs.is_in_stack(&TestStates::B));
+    }
+
+    #[test]
+    fn waits_for_timed_transitions() {
+        let mut gs = GameState::<TestStates>::default()
+            .with_transition(SceneTransition::fade(Color::BLACK, 1.));
+        gs.set_transition(TestStates::A);
+        gs.advance(0.1);
+        assert_eq!(gs.status, GameStatus::Entering);
+        assert!(gs.is_phase_start());
+
+        gs.advance(0.5);
+        assert_eq!(gs.status, GameStatus::Entering);
+        assert!((gs.transition_coverage() - 0.5).abs() < 0.001);
+
+        gs.advance(0.5);
+        assert_eq!(gs.transition_progress(), 1.);
+        gs.advance(0.1);
+        assert_eq!(gs.status, GameStatus::Running);
+
+        gs.set_transition(TestStates::B);
+        gs.set_hold("loading", true);
+        gs.advance(0.1);
+        assert_eq!(gs.status, GameStatus::Exiting);
+        gs.advance(1.);
+        gs.advance(0.1);
+        assert_eq!(gs.status, GameStatus::Exiting);
+        assert_eq!(gs.transition_coverage(), 1.);
+        assert!(!gs.is_transition_finishing());
+
+        gs.set_hold("loading", false);
+        gs.advance(0.1);
+        assert!(gs.is_transition_finishing());
+        gs.advance(0.1);
+        assert!(gs.is_in_scene(&TestStates::B));
+        assert_eq!(gs.status, GameStatus::Entering);
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum LevelStates {
+        Menu,
+        Level { name: String },
+    }
+
+    #[test]
+    fn reenters_scenes_with_new_payloads() {
+        let level = |name: &str| LevelStates::Level {
+            name: String::from(name),
+        };
+
+        let mut gs = GameState::<LevelStates>::default();
+        gs.set_transition(level("forest"));
+        gs.update();
+        gs.update();
+        assert!(gs.is_in_scene(&LevelStates::Level {
+            name: String::new()
+        }));
+        assert!(!gs.is_in_scene(&LevelStates::Menu));
+
+        gs.set_transition(level("caves"));
+        gs.update();
+        assert_eq!(gs.status, GameStatus::Exiting);
+        assert_eq!(gs.current_scene(), Some(&level("forest")));
+
+        gs.update();
+        assert_eq!(gs.status, GameStatus::Entering);
+        assert_eq!(gs.current_scene(), Some(&level("caves")));
+        assert_eq!(gs.history.back().unwrap().from, Some(level("forest")));
+    }
+
+    #[test]
+    fn queues_guards_and_records_transitions() {
+        let mut gs = GameState::<TestStates>::default()
+            .with_history_limit(4)
+            .with_guard(|from, change| match (from, change) {
+                (Some(TestStates::B), SceneChange::Pop) => Err(String::from("saving")),
+                _ => Ok(()),
+            });
+        gs.set_transition(TestStates::A);
+        gs.push(TestStates::B);
+        gs.pop();
+
+        gs.update();
+        gs.update();
+        assert!(gs.is_in_scene(&TestStates::A));
+        assert_eq!(gs.pending.len(), 2);
+
+        gs.update();
+        assert!(gs.is_in_scene(&TestStates::B));
+        gs.update();
+        gs.update();
+        assert!(gs.is_in_scene(&TestStates::B));
+        assert!(gs.pending.is_empty());
+
+        // the history is limited, but every event is kept until it is taken
+        let events = gs.take_events();
+        assert_eq!(events.len(), 5);
+        assert_eq!(
+            events[4],
+            SceneTransitionEvent {
+                from: Some(TestStates::B),
+                to: Some(TestStates::A),
+                phase: TransitionPhase::Vetoed(String::from("saving")),
+            }
+        );
+        assert_eq!(gs.history.len(), 4);
+        assert!(gs.take_events().is_empty());
+    }
+
+    #[test]
+    fn aborts_exits() {
+        let mut gs = GameState::<TestStates>::default();
+        gs.set_transition(TestStates::A);
+        gs.update();
+        gs.update();
+
+        gs.set_transition(TestStates::B);
+        gs.update();
+        assert_eq!(gs.status, GameStatus::Exiting);
+
+        gs.abort_exit("saving");
+        gs.update();
+        assert!(gs.is_in_scene(&TestStates::A));
+        assert_eq!(gs.status, GameStatus::Running);
+        assert!(gs.pending.is_empty());
+        assert_eq!(
+            gs.take_events().pop().map(|event| event.phase),
+            Some(TransitionPhase::Vetoed(String::from("saving")))
+        );
+    }
+
+    #[test]
+    fn sends_events_without_a_history() {
+        let mut gs = GameState::<TestStates>::default().with_history_limit(0);
+        gs.set_transition(TestStates::A);
+        gs.update();
+        gs.update();
+
+        assert!(gs.history.is_empty());
+        assert_eq!(gs.take_events().len(), 2);
+    }
+
+    #[test]
+    fn replacing_exits_the_paused_scenes() {
+        let mut gs = GameState::<TestStates>::default();
+        gs.set_transition(TestStates::A);
+        gs.update();
+        gs.update();
+        gs.push(TestStates::B);
+        gs.update();
+        gs.update();
+        assert!(gs.should_run(&TestStates::A, &GameStatus::Paused));
+        assert!(!gs.should_run(&TestStates::B, &GameStatus::Paused));
+
+        gs.set_transition(TestStates::C);
+        gs.update();
+        assert!(gs.should_run(&TestStates::B, &GameStatus::Exiting));
+
+        // the paused scene exits after the pushed scene, so its exit systems run too
+        gs.update();
+        assert!(gs.is_in_scene(&TestStates::A));
+        assert!(gs.should_run(&TestStates::A, &GameStatus::Exiting));
+        assert_eq!(gs.transition_coverage(), 1.);
+
+        gs.update();
+        assert!(gs.is_in_scene(&TestStates::C));
+        assert_eq!(gs.status, GameStatus::Entering);
+        assert_eq!(gs.depth(), 1);
+        assert!(!gs.is_in_stack(&TestStates::A));
+    }
+
+    #[test]
+    fn rejects_pops_without_a_scene() {
+        let mut gs = GameState::<TestStates>::default();
+        gs.pop();
+        gs.update();
+
+        assert_eq!(gs.status, GameStatus::Idle);
+        assert!(gs.pending.is_empty());
+        assert_eq!(
+            gs.take_events().pop().map(|event| event.phase),
+            Some(TransitionPhase::Vetoed(String::from(
+                "there are no scenes to pop"
+            )))
+        );
+    }
 }
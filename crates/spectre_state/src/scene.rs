@@ -0,0 +1,405 @@
+use bevy::{
+    ecs::{ArchetypeAccess, System, SystemId, ThreadLocalExecution, TypeAccess},
+    prelude::*,
+};
+use core::fmt::Debug;
+use std::{any::TypeId, borrow::Cow};
+
+use crate::{GameState, GameStatus};
+
+/// A scene in a game, which registers the systems that run as it is entered,
+/// while it is running and as it is exited. Add scenes with a `ScenePlugin`.
+pub trait Scene: Send + Sync + 'static {
+    /// The game's scene enum, which is used in the `GameState` resource
+    type Scenes: Clone + Debug + Send + Sync + 'static;
+
+    /// The value of the scene in the game's scene enum. Only the variant is matched,
+    /// so for scenes with a payload like `Game { level: u32 }` any payload can be used.
+    fn scene(&self) -> Self::Scenes;
+
+    /// Systems which run for a single frame as the scene starts entering
+    fn on_enter(&self) -> Vec<Box<dyn System>> {
+        Vec::new()
+    }
+
+    /// Systems which run every frame while the scene is the current, running scene
+    fn on_update(&self) -> Vec<Box<dyn System>> {
+        Vec::new()
+    }
+
+    /// Systems which run for a single frame once the scene's exit transition has finished
+    fn on_exit(&self) -> Vec<Box<dyn System>> {
+        Vec::new()
+    }
+
+    /// Systems which run every frame while the scene is paused below a pushed scene
+    fn on_pause(&self) -> Vec<Box<dyn System>> {
+        Vec::new()
+    }
+}
+
+/// Registers the systems of a scene so they only run when the scene is current and in the right status
+pub struct ScenePlugin<TScene> {
+    scene: TScene,
+}
+
+impl<TScene: Scene> ScenePlugin<TScene> {
+    pub fn new(scene: TScene) -> Self {
+        ScenePlugin { scene }
+    }
+}
+
+impl<TScene: Scene> Plugin for ScenePlugin<TScene> {
+    fn build(&self, app: &mut AppBuilder) {
+        let scene = self.scene.scene();
+        let phases = vec![
+            (self.scene.on_enter(), GameStatus::Entering),
+            (self.scene.on_update(), GameStatus::Running),
+            (self.scene.on_exit(), GameStatus::Exiting),
+            (self.scene.on_pause(), GameStatus::Paused),
+        ];
+
+        for (systems, status) in phases.into_iter() {
+            for system in systems.into_iter() {
+                app.add_system(SceneSystem::new(scene.clone(), status, system));
+            }
+        }
+
+        app.add_system(SceneSystem::new(
+            scene,
+            GameStatus::Exiting,
+            despawn_scene_owned::<TScene::Scenes>.system(),
+        ));
+    }
+}
+
+/// A component for entities which belong to a scene. They are recursively despawned
+/// once the scene's exit transition has finished, unless they are also `Persistent`.
+/// Payloads aren't compared, so entities owned by `Game { level: 1 }` are despawned
+/// when moving to `Game { level: 2 }`.
+/// Entities in a paused scene are kept until the scene exits.
+pub struct SceneOwned<TScene>(pub TScene);
+
+/// A component which stops a `SceneOwned` entity from being despawned when its scene exits.
+/// Persistent children of a despawned entity are detached from it rather than despawned.
+pub struct Persistent;
+
+fn despawn_scene_owned<TScene>(
+    mut commands: Commands,
+    game_state: Res<GameState<TScene>>,
+    mut owned: Query<(
+        Entity,
+        &SceneOwned<TScene>,
+        Option<&Parent>,
+        Option<&Persistent>,
+    )>,
+    owners: Query<&SceneOwned<TScene>>,
+    mut children: Query<&mut Children>,
+    persistent: Query<&Persistent>,
+) where
+    TScene: Clone + Debug + Send + Sync + 'static,
+{
+    let scene = match game_state.current_scene() {
+        Some(scene) => scene,
+        None => return,
+    };
+    let is_exiting = |owner: &SceneOwned<TScene>| {
+        std::mem::discriminant(&owner.0) == std::mem::discriminant(scene)
+    };
+
+    for (entity, owner, parent, persistent) in &mut owned.iter() {
+        if !is_exiting(owner) || persistent.is_some() {
+            continue;
+        }
+
+        // children are despawned with their parent
+        if let Some(parent) = parent {
+            if let Ok(parent_owner) = owners.get::<SceneOwned<TScene>>(parent.0) {
+                if is_exiting(&parent_owner) {
+                    continue;
+                }
+            }
+        }
+
+        detach_persistent_descendants(&mut commands, entity, &mut children, &persistent);
+        commands.despawn_recursive(entity);
+    }
+}
+
+/// Removes persistent entities from the hierarchy below the given entity, so they
+/// aren't despawned with it. Descendants of persistent entities are kept too.
+fn detach_persistent_descendants(
+    commands: &mut Commands,
+    entity: Entity,
+    children: &mut Query<&mut Children>,
+    persistent: &Query<&Persistent>,
+) {
+    let child_entities: Vec<Entity> = match children.get::<Children>(entity) {
+        Ok(entity_children) => entity_children.iter().cloned().collect(),
+        Err(_) => return,
+    };
+
+    for child in child_entities.into_iter() {
+        if persistent.get::<Persistent>(child).is_err() {
+            detach_persistent_descendants(commands, child, children, persistent);
+            continue;
+        }
+
+        // the children are read when the despawn is applied, so the child is removed straight away
+        if let Ok(mut entity_children) = children.get_mut::<Children>(entity) {
+            entity_children.0.retain(|sibling| *sibling != child);
+        }
+        commands.remove_one::<Parent>(child);
+    }
+}
+
+/// Wraps a system so it only runs when the given scene is current and in the given status,
+/// see `GameState::should_run`
+pub struct SceneSystem<TScene: Clone + Debug> {
+    scene: TScene,
+    status: GameStatus,
+    system: Box<dyn System>,
+    resource_access: TypeAccess,
+}
+
+impl<TScene> SceneSystem<TScene>
+where
+    TScene: Clone + Debug + Send + Sync + 'static,
+{
+    pub fn new(scene: TScene, status: GameStatus, system: Box<dyn System>) -> Box<dyn System> {
+        let resource_access = Self::wrapped_resource_access(system.as_ref());
+
+        Box::new(SceneSystem {
+            scene,
+            status,
+            system,
+            resource_access,
+        })
+    }
+
+    /// returns the resource access of the wrapped system, plus reading the game state to decide
+    /// whether it runs. Systems which already write the game state keep their mutable access.
+    fn wrapped_resource_access(system: &dyn System) -> TypeAccess {
+        let mut resource_access = system.resource_access().clone();
+        let game_state = TypeId::of::<GameState<TScene>>();
+        if !resource_access.mutable.contains(&game_state) {
+            resource_access.immutable.insert(game_state);
+        }
+
+        resource_access
+    }
+
+    fn should_run(&self, resources: &Resources) -> bool {
+        match resources.get::<GameState<TScene>>() {
+            Some(game_state) => game_state.should_run(&self.scene, &self.status),
+            None => false,
+        }
+    }
+}
+
+impl<TScene> System for SceneSystem<TScene>
+where
+    TScene: Clone + Debug + Send + Sync + 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        self.system.name()
+    }
+
+    fn id(&self) -> SystemId {
+        self.system.id()
+    }
+
+    fn update_archetype_access(&mut self, world: &World) {
+        self.system.update_archetype_access(world);
+        self.resource_access = Self::wrapped_resource_access(self.system.as_ref());
+    }
+
+    fn archetype_access(&self) -> &ArchetypeAccess {
+        self.system.archetype_access()
+    }
+
+    fn resource_access(&self) -> &TypeAccess {
+        &self.resource_access
+    }
+
+    fn thread_local_execution(&self) -> ThreadLocalExecution {
+        self.system.thread_local_execution()
+    }
+
+    fn run(&mut self, world: &World, resources: &Resources) {
+        if self.should_run(resources) {
+            self.system.run(world, resources);
+        }
+    }
+
+    fn run_thread_local(&mut self, world: &mut World, resources: &mut Resources) {
+        // thread local systems do their work here, other systems apply the commands
+        // buffered when they ran, so there is nothing to apply if they didn't run
+        if let ThreadLocalExecution::Immediate = self.thread_local_execution() {
+            if !self.should_run(resources) {
+                return;
+            }
+        }
+
+        self.system.run_thread_local(world, resources);
+    }
+
+    fn initialize(&mut self, resources: &mut Resources) {
+        self.system.initialize(resources);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum TestScenes {
+        A,
+        B,
+    }
+
+    #[derive(Default)]
+    struct Runs(usize);
+
+    fn count_runs(mut runs: ResMut<Runs>) {
+        runs.0 += 1;
+    }
+
+    fn leave_scene(mut game_state: ResMut<GameState<TestScenes>>) {
+        game_state.set_transition(TestScenes::B);
+    }
+
+    fn run_system(system: &mut Box<dyn System>, world: &mut World, resources: &mut Resources) {
+        system.update_archetype_access(world);
+        system.run(world, resources);
+        system.run_thread_local(world, resources);
+    }
+
+    fn despawn_exiting_scene(world: &mut World, resources: &mut Resources) {
+        let mut game_state = GameState::<TestScenes>::default();
+        game_state.set_transition(TestScenes::A);
+        game_state.update();
+        game_state.update();
+        game_state.set_transition(TestScenes::B);
+        game_state.update();
+        resources.insert(game_state);
+
+        let mut system = despawn_scene_owned::<TestScenes>.system();
+        system.initialize(resources);
+        run_system(&mut system, world, resources);
+    }
+
+    fn update_game_state(resources: &Resources) {
+        resources
+            .get_mut::<GameState<TestScenes>>()
+            .unwrap()
+            .update();
+    }
+
+    #[test]
+    fn despawns_entities_owned_by_the_exiting_scene() {
+        let mut world = World::new();
+        let mut resources = Resources::default();
+
+        let owned = world.spawn((SceneOwned(TestScenes::A),));
+        let child = world.spawn((Parent(owned),));
+        world.insert_one(owned, Children::with(&[child])).unwrap();
+        let other_scene = world.spawn((SceneOwned(TestScenes::B),));
+        let persistent = world.spawn((SceneOwned(TestScenes::A), Persistent));
+
+        despawn_exiting_scene(&mut world, &mut resources);
+
+        assert!(!world.contains(owned));
+        assert!(!world.contains(child));
+        assert!(world.contains(other_scene));
+        assert!(world.contains(persistent));
+    }
+
+    #[test]
+    fn keeps_persistent_children() {
+        let mut world = World::new();
+        let mut resources = Resources::default();
+
+        let owned = world.spawn((SceneOwned(TestScenes::A),));
+        let persistent_child = world.spawn((Parent(owned), Persistent));
+        let child = world.spawn((Parent(owned),));
+        let persistent_grandchild = world.spawn((Parent(child), Persistent));
+        world
+            .insert_one(owned, Children::with(&[persistent_child, child]))
+            .unwrap();
+        world
+            .insert_one(child, Children::with(&[persistent_grandchild]))
+            .unwrap();
+
+        despawn_exiting_scene(&mut world, &mut resources);
+
+        assert!(!world.contains(owned));
+        assert!(!world.contains(child));
+        assert!(world.contains(persistent_child));
+        assert!(world.contains(persistent_grandchild));
+        assert!(world.get::<Parent>(persistent_child).is_err());
+        assert!(world.get::<Parent>(persistent_grandchild).is_err());
+    }
+
+    #[test]
+    fn keeps_mutable_game_state_access() {
+        let game_state = TypeId::of::<GameState<TestScenes>>();
+
+        let system = SceneSystem::new(TestScenes::A, GameStatus::Running, leave_scene.system());
+        assert!(system.resource_access().mutable.contains(&game_state));
+        assert!(!system.resource_access().immutable.contains(&game_state));
+
+        let system = SceneSystem::new(TestScenes::A, GameStatus::Running, count_runs.system());
+        assert!(system.resource_access().immutable.contains(&game_state));
+        assert!(system
+            .resource_access()
+            .mutable
+            .contains(&TypeId::of::<Runs>()));
+    }
+
+    #[test]
+    fn runs_in_its_scene_and_status() {
+        let mut world = World::new();
+        let mut resources = Resources::default();
+        resources.insert(GameState::<TestScenes>::default());
+        resources.insert(Runs::default());
+
+        let mut system = SceneSystem::new(TestScenes::A, GameStatus::Running, count_runs.system());
+        system.initialize(&mut resources);
+
+        run_system(&mut system, &mut world, &mut resources);
+        assert_eq!(resources.get::<Runs>().unwrap().0, 0);
+
+        resources
+            .get_mut::<GameState<TestScenes>>()
+            .unwrap()
+            .set_transition(TestScenes::A);
+        update_game_state(&resources);
+        run_system(&mut system, &mut world, &mut resources);
+        assert_eq!(resources.get::<Runs>().unwrap().0, 0);
+
+        update_game_state(&resources);
+        run_system(&mut system, &mut world, &mut resources);
+        assert_eq!(resources.get::<Runs>().unwrap().0, 1);
+    }
+
+    #[test]
+    fn wrapped_systems_can_change_the_game_state() {
+        let mut world = World::new();
+        let mut resources = Resources::default();
+        let mut game_state = GameState::<TestScenes>::default();
+        game_state.set_transition(TestScenes::A);
+        game_state.update();
+        game_state.update();
+        resources.insert(game_state);
+
+        let mut system = SceneSystem::new(TestScenes::A, GameStatus::Running, leave_scene.system());
+        system.initialize(&mut resources);
+        run_system(&mut system, &mut world, &mut resources);
+
+        update_game_state(&resources);
+        let game_state = resources.get::<GameState<TestScenes>>().unwrap();
+        assert_eq!(game_state.status, GameStatus::Exiting);
+    }
+}
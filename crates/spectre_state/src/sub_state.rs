@@ -0,0 +1,292 @@
+use bevy::prelude::*;
+use core::fmt::Debug;
+use std::any::type_name;
+
+use crate::{GameState, GameStatus, SceneChange};
+
+/// Runs a nested `GameState<TSub>` inside a scene of the parent state, e.g. exploring,
+/// combat and dialogue inside the game scene. Sub states have the same lifecycle, guards
+/// and transitions as the parent state, and their scenes are added with a `ScenePlugin`.
+///
+/// The sub state starts in the initial scene once the parent scene is running, and is
+/// suspended while the parent scene is paused or entering. When the parent scene exits,
+/// every sub scene is popped before the parent scene finishes exiting. If a guard on the
+/// sub state vetoes a pop, the parent scene's exit is aborted, see `GameState::abort_exit`.
+///
+/// The parent scene is matched by its variant, so its payload is ignored.
+/// Sub states can have sub states of their own, and a scene can have several sub states
+/// with different enums, which run in parallel. Adding plugins with the same sub enum
+/// for different parent scenes shares the sub state between them.
+pub struct SubStatePlugin<TParent, TSub> {
+    parent: TParent,
+    initial: TSub,
+}
+
+impl<TParent, TSub> SubStatePlugin<TParent, TSub>
+where
+    TParent: Clone + Debug + Send + Sync + 'static,
+    TSub: Clone + Debug + Send + Sync + 'static,
+{
+    pub fn new(parent: TParent, initial: TSub) -> Self {
+        SubStatePlugin { parent, initial }
+    }
+}
+
+impl<TParent, TSub> Plugin for SubStatePlugin<TParent, TSub>
+where
+    TParent: Clone + Debug + Send + Sync + 'static,
+    TSub: Clone + Debug + Send + Sync + 'static,
+{
+    fn build(&self, app: &mut AppBuilder) {
+        // the sub state may already have been added with a transition or guards
+        if app.resources().get::<GameState<TSub>>().is_none() {
+            app.add_resource(GameState::<TSub>::default());
+        }
+
+        let parent = (self.parent.clone(), self.initial.clone());
+        if let Some(mut parents) = app.resources().get_mut::<SubStateParents<TParent, TSub>>() {
+            parents.parents.push(parent);
+            return;
+        }
+
+        app.add_resource(SubStateParents {
+            parents: vec![parent],
+        })
+        .add_system(sub_state_transitions::<TParent, TSub>.system());
+    }
+}
+
+/// The parent scenes of a sub state, and the scene it starts in under each of them
+struct SubStateParents<TParent, TSub> {
+    parents: Vec<(TParent, TSub)>,
+}
+
+fn sub_state_transitions<TParent, TSub>(
+    time: Res<Time>,
+    parents: Res<SubStateParents<TParent, TSub>>,
+    mut parent: ResMut<GameState<TParent>>,
+    mut sub_state: ResMut<GameState<TSub>>,
+) where
+    TParent: Clone + Debug + Send + Sync + 'static,
+    TSub: Clone + Debug + Send + Sync + 'static,
+{
+    let hold = type_name::<TSub>();
+    let initial = parents
+        .parents
+        .iter()
+        .find(|(scene, _)| !parent.suspended && parent.is_in_scene(scene))
+        .map(|(_, initial)| initial.clone());
+    let in_parent = initial.is_some();
+
+    // the parent is held while there are sub scenes, so it can't finish exiting until they are popped
+    parent.set_hold(hold, in_parent && sub_state.depth() > 0);
+
+    if in_parent && parent.is_in_status(&GameStatus::Exiting) {
+        if sub_state.depth() == 0 {
+            return;
+        }
+
+        sub_state.suspended = false;
+        if !sub_state.is_in_status(&GameStatus::Running) {
+            sub_state.advance(time.delta_seconds);
+            return;
+        }
+
+        // changes queued in the sub state are dropped, as its scenes are being left
+        let transition = sub_state.transition;
+        sub_state.pending.clear();
+        sub_state.change_scene(SceneChange::Pop, transition);
+        sub_state.advance(time.delta_seconds);
+
+        // a vetoed pop is dropped without leaving the sub scene, so the parent stays too
+        // rather than asking again every frame
+        if sub_state.is_in_status(&GameStatus::Running) && sub_state.pending.is_empty() {
+            parent.abort_exit(&format!("{} vetoed leaving its scene", hold));
+        }
+        return;
+    }
+
+    sub_state.suspended = !(in_parent && parent.is_in_status(&GameStatus::Running));
+    if sub_state.suspended {
+        return;
+    }
+
+    if sub_state.is_in_status(&GameStatus::Idle) && sub_state.pending.is_empty() {
+        if let Some(initial) = initial {
+            let transition = sub_state.transition;
+            sub_state.change_scene(SceneChange::Replace(initial), transition);
+        }
+    }
+
+    sub_state.advance(time.delta_seconds);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SceneTransitionEvent, TransitionPhase};
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Scenes {
+        Menu,
+        Game,
+        Paused,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Modes {
+        Exploring,
+        Combat,
+    }
+
+    struct TestApp {
+        world: World,
+        resources: Resources,
+        system: Box<dyn System>,
+    }
+
+    impl TestApp {
+        fn new(sub_state: GameState<Modes>) -> Self {
+            let mut resources = Resources::default();
+            resources.insert(Time::default());
+            resources.insert(GameState::<Scenes>::default());
+            resources.insert(sub_state);
+            resources.insert(SubStateParents {
+                parents: vec![(Scenes::Game, Modes::Exploring)],
+            });
+
+            let mut system = sub_state_transitions::<Scenes, Modes>.system();
+            system.initialize(&mut resources);
+
+            TestApp {
+                world: World::new(),
+                resources,
+                system,
+            }
+        }
+
+        fn parent<R>(&self, f: impl FnOnce(&mut GameState<Scenes>) -> R) -> R {
+            f(&mut self.resources.get_mut::<GameState<Scenes>>().unwrap())
+        }
+
+        fn sub_state<R>(&self, f: impl FnOnce(&mut GameState<Modes>) -> R) -> R {
+            f(&mut self.resources.get_mut::<GameState<Modes>>().unwrap())
+        }
+
+        /// Runs a frame, updating the parent state before the sub state
+        fn update(&mut self) {
+            self.parent(|parent| parent.update());
+            self.system.update_archetype_access(&self.world);
+            self.system.run(&self.world, &self.resources);
+            self.system
+                .run_thread_local(&mut self.world, &mut self.resources);
+        }
+
+        /// Enters the game scene and runs until the sub state is running
+        fn start_game(&mut self) {
+            self.parent(|parent| parent.set_transition(Scenes::Game));
+            for _ in 0..4 {
+                self.update();
+            }
+        }
+    }
+
+    fn count_vetoes<TScene>(events: &[SceneTransitionEvent<TScene>]) -> usize {
+        events
+            .iter()
+            .filter(|event| match event.phase {
+                TransitionPhase::Vetoed(_) => true,
+                _ => false,
+            })
+            .count()
+    }
+
+    #[test]
+    fn starts_once_the_parent_is_running() {
+        let mut app = TestApp::new(GameState::default());
+        app.parent(|parent| parent.set_transition(Scenes::Game));
+
+        app.update();
+        assert_eq!(app.parent(|parent| parent.status), GameStatus::Entering);
+        assert!(app.sub_state(|sub_state| sub_state.suspended));
+        assert_eq!(app.sub_state(|sub_state| sub_state.depth()), 0);
+
+        app.update();
+        app.update();
+        assert!(app.sub_state(|sub_state| sub_state.is_in_scene(&Modes::Exploring)));
+        assert_eq!(
+            app.sub_state(|sub_state| sub_state.status),
+            GameStatus::Running
+        );
+    }
+
+    #[test]
+    fn suspends_while_the_parent_is_paused() {
+        let mut app = TestApp::new(GameState::default());
+        app.start_game();
+
+        app.parent(|parent| parent.push(Scenes::Paused));
+        app.update();
+        assert!(app.sub_state(|sub_state| sub_state.suspended));
+        assert!(app.sub_state(|sub_state| sub_state.is_in_scene(&Modes::Exploring)));
+
+        app.parent(|parent| parent.pop());
+        for _ in 0..4 {
+            app.update();
+        }
+        assert!(app.parent(|parent| parent.is_in_scene(&Scenes::Game)));
+        assert!(!app.sub_state(|sub_state| sub_state.suspended));
+    }
+
+    #[test]
+    fn pops_sub_scenes_before_the_parent_exits() {
+        let mut app = TestApp::new(GameState::default());
+        app.start_game();
+        app.sub_state(|sub_state| sub_state.push(Modes::Combat));
+        app.update();
+        app.update();
+        assert_eq!(app.sub_state(|sub_state| sub_state.depth()), 2);
+
+        app.parent(|parent| parent.set_transition(Scenes::Menu));
+        for _ in 0..10 {
+            app.update();
+            if app.sub_state(|sub_state| sub_state.depth()) > 0 {
+                assert!(app.parent(|parent| parent.is_in_scene(&Scenes::Game)));
+            }
+        }
+
+        assert!(app.parent(|parent| parent.is_in_scene(&Scenes::Menu)));
+        assert_eq!(app.sub_state(|sub_state| sub_state.depth()), 0);
+    }
+
+    #[test]
+    fn aborts_the_parent_exit_when_a_pop_is_vetoed() {
+        let sub_state = GameState::default().with_guard(|from, change| match (from, change) {
+            (Some(Modes::Combat), SceneChange::Pop) => Err(String::from("in combat")),
+            _ => Ok(()),
+        });
+        let mut app = TestApp::new(sub_state);
+        app.start_game();
+        app.sub_state(|sub_state| sub_state.set_transition(Modes::Combat));
+        for _ in 0..3 {
+            app.update();
+        }
+        app.parent(|parent| parent.take_events());
+        app.sub_state(|sub_state| sub_state.take_events());
+
+        app.parent(|parent| parent.set_transition(Scenes::Menu));
+        for _ in 0..5 {
+            app.update();
+        }
+
+        assert!(app.parent(|parent| parent.is_in_scene(&Scenes::Game)));
+        assert_eq!(app.parent(|parent| parent.status), GameStatus::Running);
+        assert!(app.sub_state(|sub_state| sub_state.is_in_scene(&Modes::Combat)));
+
+        // the veto is reported once, rather than every frame
+        let parent_events = app.parent(|parent| parent.take_events());
+        assert_eq!(count_vetoes(&parent_events), 1);
+        let sub_events = app.sub_state(|sub_state| sub_state.take_events());
+        assert_eq!(count_vetoes(&sub_events), 1);
+    }
+}
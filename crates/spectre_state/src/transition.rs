@@ -0,0 +1,313 @@
+use bevy::prelude::*;
+use core::fmt::Debug;
+use std::marker::PhantomData;
+
+use crate::{GameState, SceneOwned};
+
+/// The edge a wipe moves towards as it covers the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WipeDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// The effect drawn while a scene exits and the next scene enters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransitionEffect {
+    /// Nothing is drawn
+    None,
+
+    /// Fades the screen to the color as the scene exits, then back as the next scene enters
+    Fade(Color),
+
+    /// Fades the text and sprites of the exiting scene out, then those of the entering scene in.
+    /// Only one scene is shown at a time, so this fades through an empty screen.
+    FadeThrough,
+
+    /// Covers the screen with the color, moving in the direction as the scene exits
+    /// and uncovering it in the same direction as the next scene enters
+    Wipe(Color, WipeDirection),
+
+    /// Nothing is drawn by the `SceneTransitionPlugin`, systems can draw the named
+    /// effect using `GameState::transition_coverage`
+    Custom(&'static str),
+}
+
+/// How a scene change is shown. The exit and enter phases each last `duration` seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SceneTransition {
+    pub effect: TransitionEffect,
+    pub duration: f32,
+}
+
+impl Default for SceneTransition {
+    /// An instant transition, which takes a frame per phase
+    fn default() -> Self {
+        SceneTransition {
+            effect: TransitionEffect::None,
+            duration: 0.,
+        }
+    }
+}
+
+impl SceneTransition {
+    pub fn new(effect: TransitionEffect, duration: f32) -> Self {
+        SceneTransition { effect, duration }
+    }
+
+    pub fn fade(color: Color, duration: f32) -> Self {
+        SceneTransition::new(TransitionEffect::Fade(color), duration)
+    }
+
+    pub fn fade_through(duration: f32) -> Self {
+        SceneTransition::new(TransitionEffect::FadeThrough, duration)
+    }
+
+    pub fn wipe(color: Color, direction: WipeDirection, duration: f32) -> Self {
+        SceneTransition::new(TransitionEffect::Wipe(color, direction), duration)
+    }
+}
+
+/// The part of a scene change a `SceneTransitionEvent` reports
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransitionPhase {
+    /// The scene being left started exiting
+    Exiting,
+
+    /// The next scene started entering
+    Entering,
+
+    /// The change is done and the next scene is running, or there are no scenes left
+    Finished,
+
+    /// A guard stopped the change, for the given reason
+    Vetoed(String),
+}
+
+/// An event sent as a scene change moves through its phases
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneTransitionEvent<TScene> {
+    pub from: Option<TScene>,
+    pub to: Option<TScene>,
+    pub phase: TransitionPhase,
+}
+
+/// Sends `SceneTransitionEvent`s and draws the built in transition effects for the game's scene enum
+pub struct SceneTransitionPlugin<TScene> {
+    marker: PhantomData<fn() -> TScene>,
+}
+
+impl<TScene> Default for SceneTransitionPlugin<TScene> {
+    fn default() -> Self {
+        SceneTransitionPlugin {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<TScene> Plugin for SceneTransitionPlugin<TScene>
+where
+    TScene: Clone + Debug + Send + Sync + 'static,
+{
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<SceneTransitionEvent<TScene>>()
+            .add_system(send_transition_events::<TScene>.system())
+            .add_system(transition_overlay::<TScene>.system())
+            .add_system(fade_through_scene::<TScene>.system())
+            // bevy sets the z of UI nodes in post update, so the overlay is moved on top after it
+            .add_stage_after("post_update", "transition_overlay")
+            .add_system_to_stage("transition_overlay", raise_transition_overlay.system());
+    }
+}
+
+fn send_transition_events<TScene>(
+    mut game_state: ResMut<GameState<TScene>>,
+    mut events: ResMut<Events<SceneTransitionEvent<TScene>>>,
+) where
+    TScene: Clone + Debug + Send + Sync + 'static,
+{
+    for event in game_state.take_events().into_iter() {
+        events.send(event);
+    }
+}
+
+/// A full screen UI node used to draw fades and wipes
+pub struct TransitionOverlay {
+    material: Handle<ColorMaterial>,
+}
+
+fn transition_overlay<TScene>(
+    mut commands: Commands,
+    game_state: Res<GameState<TScene>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut overlays: Query<(&TransitionOverlay, &mut Style)>,
+) where
+    TScene: Clone + Debug + Send + Sync + 'static,
+{
+    let coverage = game_state.transition_coverage();
+    let (color, wipe) = match game_state.active_transition().effect {
+        TransitionEffect::Fade(color) => (color, None),
+        TransitionEffect::Wipe(color, direction) => (color, Some(direction)),
+        _ => (Color::NONE, None),
+    };
+
+    let mut has_overlay = false;
+    for (overlay, mut style) in &mut overlays.iter() {
+        has_overlay = true;
+
+        if let Some(material) = materials.get_mut(&overlay.material) {
+            material.color = color;
+            if wipe.is_none() {
+                material.color.a *= coverage;
+            }
+        }
+
+        *style = overlay_style(wipe, coverage);
+    }
+
+    // the overlay is only spawned once it is needed, and then kept
+    if !has_overlay && coverage > 0. && color.a > 0. {
+        let material = materials.add(Color::NONE.into());
+        commands
+            .spawn(NodeComponents {
+                style: overlay_style(wipe, 0.),
+                material,
+                ..Default::default()
+            })
+            .with(TransitionOverlay { material });
+    }
+}
+
+/// The z of the transition overlay, in front of the other UI nodes. Bevy orders UI nodes by
+/// their place in the hierarchy, which doesn't put the overlay on top as it is spawned late.
+const OVERLAY_Z: f32 = 100.;
+
+fn raise_transition_overlay(mut overlays: Query<(&TransitionOverlay, &mut Transform)>) {
+    for (_, mut transform) in &mut overlays.iter() {
+        let mut translation = transform.translation();
+        translation.set_z(OVERLAY_Z);
+        transform.set_translation(translation);
+    }
+}
+
+/// returns the style of an overlay covering the given fraction of the screen
+fn overlay_style(wipe: Option<WipeDirection>, coverage: f32) -> Style {
+    let full = Val::Percent(100.);
+    let covered = Val::Percent(coverage * 100.);
+    let edge = Val::Px(0.);
+
+    let (size, position) = match wipe {
+        None => (Size::new(full, full), Rect::all(edge)),
+        // the wipe is anchored to the edge opposite the direction it moves in
+        Some(WipeDirection::Right) => (
+            Size::new(covered, full),
+            Rect {
+                left: edge,
+                top: edge,
+                ..Default::default()
+            },
+        ),
+        Some(WipeDirection::Left) => (
+            Size::new(covered, full),
+            Rect {
+                right: edge,
+                top: edge,
+                ..Default::default()
+            },
+        ),
+        Some(WipeDirection::Down) => (
+            Size::new(full, covered),
+            Rect {
+                left: edge,
+                top: edge,
+                ..Default::default()
+            },
+        ),
+        Some(WipeDirection::Up) => (
+            Size::new(full, covered),
+            Rect {
+                left: edge,
+                bottom: edge,
+                ..Default::default()
+            },
+        ),
+    };
+
+    Style {
+        position_type: PositionType::Absolute,
+        position,
+        size,
+        ..Default::default()
+    }
+}
+
+/// The alpha an entity had before a fade through changed it, which is restored once the fade is over
+struct FadedAlpha(f32);
+
+/// Fades the current scene's text and sprites during a fade through, multiplying their own alpha
+fn fade_through_scene<TScene>(
+    mut commands: Commands,
+    game_state: Res<GameState<TScene>>,
+    mut texts: Query<(Entity, &SceneOwned<TScene>, &mut Text, Option<&FadedAlpha>)>,
+    mut sprites: Query<(
+        Entity,
+        &SceneOwned<TScene>,
+        &mut TextureAtlasSprite,
+        Option<&FadedAlpha>,
+    )>,
+) where
+    TScene: Clone + Debug + Send + Sync + 'static,
+{
+    let visibility = match game_state.active_transition().effect {
+        TransitionEffect::FadeThrough => 1. - game_state.transition_coverage(),
+        _ => 1.,
+    };
+
+    for (entity, owner, mut text, faded) in &mut texts.iter() {
+        if game_state.is_in_scene(&owner.0) {
+            fade_alpha(
+                &mut commands,
+                entity,
+                faded,
+                &mut text.style.color.a,
+                visibility,
+            );
+        }
+    }
+
+    for (entity, owner, mut sprite, faded) in &mut sprites.iter() {
+        if game_state.is_in_scene(&owner.0) {
+            fade_alpha(
+                &mut commands,
+                entity,
+                faded,
+                &mut sprite.color.a,
+                visibility,
+            );
+        }
+    }
+}
+
+/// Multiplies the entity's own alpha by the visibility, restoring it when the fade is over
+fn fade_alpha(
+    commands: &mut Commands,
+    entity: Entity,
+    faded: Option<&FadedAlpha>,
+    alpha: &mut f32,
+    visibility: f32,
+) {
+    match faded {
+        Some(faded) if visibility >= 1. => {
+            *alpha = faded.0;
+            commands.remove_one::<FadedAlpha>(entity);
+        }
+        Some(faded) => *alpha = faded.0 * visibility,
+        None if visibility < 1. => {
+            commands.insert_one(entity, FadedAlpha(*alpha));
+            *alpha *= visibility;
+        }
+        None => {}
+    }
+}
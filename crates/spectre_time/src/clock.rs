@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+/// The clock which advances with `GameTime`, so it follows `GameSpeedRequest`s
+pub const WORLD_CLOCK: &str = "world";
+
+/// A clock which advances in real time, so menus keep running while the world is paused
+pub const UI_CLOCK: &str = "ui";
+
+/// A clock for cutscenes, which can be paused and sped up without affecting the world
+pub const CINEMATIC_CLOCK: &str = "cinematic";
+
+/// A clock with its own speed, pause state and elapsed time, see `GameClocks`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clock {
+    pub speed: f32,
+    pub is_paused: bool,
+
+    /// The time in seconds this clock has advanced by since it was added
+    pub elapsed: f32,
+
+    /// The time in seconds this clock advanced by in the current frame
+    pub delta: f32,
+
+    /// A clock with a parent advances by its parent's delta instead of real time,
+    /// so it also pauses and speeds up with its parent, e.g. bullet time in the world
+    pub parent: Option<String>,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock {
+            speed: 1.,
+            is_paused: false,
+            elapsed: 0.,
+            delta: 0.,
+            parent: None,
+        }
+    }
+}
+
+impl Clock {
+    pub fn new(speed: f32) -> Self {
+        Clock {
+            speed,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_parent(mut self, parent: &str) -> Self {
+        self.parent = Some(String::from(parent));
+        self
+    }
+
+    pub fn pause(&mut self) {
+        self.is_paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.is_paused = false;
+    }
+
+    /// returns the elapsed time of this clock once it has advanced by the given
+    /// number of seconds, e.g. to set the expiry of a buff
+    pub fn time_after(&self, seconds: f32) -> f32 {
+        self.elapsed + seconds
+    }
+
+    fn tick(&mut self, parent_delta: f32) {
+        self.delta = if self.is_paused {
+            0.
+        } else {
+            parent_delta * self.speed
+        };
+        self.elapsed += self.delta;
+    }
+}
+
+/// A resource holding named clocks, which by default are the world, UI and cinematic clocks.
+/// Clocks can be added for e.g. each player in bullet time, and entities choose the clock
+/// which drives them with a `ClockBinding`.
+pub struct GameClocks {
+    clocks: HashMap<String, Clock>,
+}
+
+impl Default for GameClocks {
+    fn default() -> Self {
+        GameClocks {
+            clocks: HashMap::new(),
+        }
+        .with_clock(WORLD_CLOCK, Clock::default())
+        .with_clock(UI_CLOCK, Clock::default())
+        .with_clock(CINEMATIC_CLOCK, Clock::default())
+    }
+}
+
+impl GameClocks {
+    pub fn with_clock(mut self, name: &str, clock: Clock) -> Self {
+        self.add(name, clock);
+        self
+    }
+
+    /// Adds a clock, replacing any clock with the same name
+    pub fn add(&mut self, name: &str, clock: Clock) {
+        self.clocks.insert(String::from(name), clock);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Clock> {
+        self.clocks.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Clock> {
+        self.clocks.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Clock> {
+        self.clocks.get_mut(name)
+    }
+
+    /// returns the clock an entity is bound to, or the world clock for unbound entities
+    pub fn bound(&self, binding: Option<&ClockBinding>) -> Option<&Clock> {
+        match binding {
+            Some(binding) => self.get(&binding.0),
+            None => self.get(WORLD_CLOCK),
+        }
+    }
+
+    /// returns the time a fixed step covers on the clock an entity is bound to, scaling the
+    /// step by the speed of that clock relative to the world clock in the current frame
+    pub fn bound_step_delta(&self, binding: Option<&ClockBinding>, fixed_delta: f32) -> f32 {
+        let world_delta = self.delta(WORLD_CLOCK);
+        if binding.is_none() || world_delta <= 0. {
+            return fixed_delta;
+        }
+
+        self.bound(binding)
+            .map_or(0., |clock| fixed_delta * clock.delta / world_delta)
+    }
+
+    /// returns the delta of the named clock in the current frame, or 0 if there is no such clock
+    pub fn delta(&self, name: &str) -> f32 {
+        self.get(name).map_or(0., |clock| clock.delta)
+    }
+
+    /// returns the elapsed time of the named clock, or 0 if there is no such clock
+    pub fn elapsed(&self, name: &str) -> f32 {
+        self.get(name).map_or(0., |clock| clock.elapsed)
+    }
+
+    /// Advances every clock. Clocks without a parent advance by the real delta, except for
+    /// the world clock which advances by the world delta. Clocks whose parent is missing don't advance.
+    pub fn advance(&mut self, real_delta: f32, world_delta: f32) {
+        let mut ticked: HashMap<String, f32> = HashMap::new();
+
+        // parents are ticked before their children
+        loop {
+            let mut progressed = false;
+            for (name, clock) in self.clocks.iter_mut() {
+                if ticked.contains_key(name) {
+                    continue;
+                }
+
+                let parent_delta = match &clock.parent {
+                    None if name == WORLD_CLOCK => world_delta,
+                    None => real_delta,
+                    Some(parent) => match ticked.get(parent) {
+                        Some(delta) => *delta,
+                        None => continue,
+                    },
+                };
+
+                clock.tick(parent_delta);
+                ticked.insert(name.clone(), clock.delta);
+                progressed = true;
+            }
+
+            if !progressed {
+                break;
+            }
+        }
+
+        for (name, clock) in self.clocks.iter_mut() {
+            if !ticked.contains_key(name) {
+                clock.delta = 0.;
+            }
+        }
+    }
+}
+
+/// A component which chooses the clock that drives an entity, entities without
+/// a binding are driven by the world clock
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClockBinding(pub String);
+
+impl ClockBinding {
+    pub fn new(name: &str) -> Self {
+        ClockBinding(String::from(name))
+    }
+}
+
+/// A cooldown measured on a named clock, so e.g. abilities recharge slower in bullet time
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cooldown {
+    pub clock: String,
+    pub duration: f32,
+    ready_at: f32,
+}
+
+impl Cooldown {
+    /// A cooldown which is ready until it is started
+    pub fn new(clock: &str, duration: f32) -> Self {
+        Cooldown {
+            clock: String::from(clock),
+            duration,
+            ready_at: 0.,
+        }
+    }
+
+    pub fn start(&mut self, clocks: &GameClocks) {
+        self.ready_at = clocks.elapsed(&self.clock) + self.duration;
+    }
+
+    /// returns the seconds left on the cooldown's clock until it is ready
+    pub fn remaining(&self, clocks: &GameClocks) -> f32 {
+        (self.ready_at - clocks.elapsed(&self.clock)).max(0.)
+    }
+
+    pub fn is_ready(&self, clocks: &GameClocks) -> bool {
+        self.remaining(clocks) <= 0.
+    }
+
+    /// Starts the cooldown if it is ready, returning true if it was started
+    pub fn try_start(&mut self, clocks: &GameClocks) -> bool {
+        if !self.is_ready(clocks) {
+            return false;
+        }
+
+        self.start(clocks);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advances_named_clocks() {
+        let mut clocks = GameClocks::default()
+            .with_clock("player_1", Clock::new(0.25).with_parent(WORLD_CLOCK))
+            .with_clock("orphan", Clock::default().with_parent("missing"));
+        clocks.get_mut(CINEMATIC_CLOCK).unwrap().pause();
+
+        clocks.advance(1., 2.);
+        assert_eq!(clocks.delta(WORLD_CLOCK), 2.);
+        assert_eq!(clocks.delta(UI_CLOCK), 1.);
+        assert_eq!(clocks.delta(CINEMATIC_CLOCK), 0.);
+        assert_eq!(clocks.delta("player_1"), 0.5);
+        assert_eq!(clocks.delta("orphan"), 0.);
+
+        // children of a paused clock stop too
+        clocks.get_mut(WORLD_CLOCK).unwrap().pause();
+        clocks.advance(1., 2.);
+        assert_eq!(clocks.elapsed(WORLD_CLOCK), 2.);
+        assert_eq!(clocks.elapsed("player_1"), 0.5);
+        assert_eq!(clocks.elapsed(UI_CLOCK), 2.);
+    }
+
+    #[test]
+    fn scales_fixed_steps_by_bound_clocks() {
+        let mut clocks =
+            GameClocks::default().with_clock("player_1", Clock::new(0.5).with_parent(WORLD_CLOCK));
+        clocks.advance(1., 2.);
+
+        let binding = ClockBinding::new("player_1");
+        assert_eq!(clocks.bound_step_delta(None, 0.1), 0.1);
+        assert_eq!(clocks.bound_step_delta(Some(&binding), 0.1), 0.05);
+        assert_eq!(
+            clocks.bound_step_delta(Some(&ClockBinding::new("missing")), 0.1),
+            0.
+        );
+    }
+
+    #[test]
+    fn measures_cooldowns_on_their_clock() {
+        let mut clocks =
+            GameClocks::default().with_clock("player_1", Clock::new(0.5).with_parent(WORLD_CLOCK));
+        let mut cooldown = Cooldown::new("player_1", 1.);
+
+        assert!(cooldown.try_start(&clocks));
+        assert!(!cooldown.try_start(&clocks));
+
+        clocks.advance(1., 1.);
+        assert_eq!(cooldown.remaining(&clocks), 0.5);
+
+        clocks.advance(1., 1.);
+        assert!(cooldown.is_ready(&clocks));
+    }
+}
@@ -0,0 +1,125 @@
+/// Splits game time into fixed steps, so systems in the fixed update stage run the same
+/// way at any frame rate and game speed. Time which doesn't fill a step is carried over
+/// to the next frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FixedTimestep {
+    /// The number of steps per game second. No steps run unless this is above zero.
+    pub tick_rate: f32,
+
+    /// The most steps run in a frame. Time beyond this is dropped, so a slow frame
+    /// slows the game down instead of making the following frames slower too.
+    pub max_steps_per_frame: u32,
+
+    accumulator: f32,
+    steps: u32,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        FixedTimestep::new(60.)
+    }
+}
+
+impl FixedTimestep {
+    pub fn new(tick_rate: f32) -> Self {
+        FixedTimestep {
+            tick_rate,
+            max_steps_per_frame: 5,
+            accumulator: 0.,
+            steps: 0,
+        }
+    }
+
+    pub fn with_max_steps_per_frame(mut self, max_steps: u32) -> Self {
+        self.max_steps_per_frame = max_steps;
+        self
+    }
+
+    /// returns the game time in seconds covered by a step
+    pub fn step_delta(&self) -> f32 {
+        1. / self.tick_rate
+    }
+
+    /// returns the number of steps to run in the current frame
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// returns how far the game time is between the last step and the next, from 0 to 1,
+    /// which is used to interpolate positions when rendering
+    pub fn alpha(&self) -> f32 {
+        if !self.has_valid_tick_rate() {
+            return 0.;
+        }
+
+        (self.accumulator / self.step_delta()).min(1.)
+    }
+
+    /// Adds the game time of a frame, returning the number of steps to run
+    pub fn advance(&mut self, delta: f32) -> u32 {
+        if !self.has_valid_tick_rate() {
+            self.accumulator = 0.;
+            self.steps = 0;
+            return 0;
+        }
+
+        let step = self.step_delta();
+        self.accumulator += delta;
+
+        // the small margin stops rounding errors from skipping a step when frames match the tick rate
+        let due = ((self.accumulator + 1e-5) / step).floor() as u32;
+        self.steps = due.min(self.max_steps_per_frame);
+        self.accumulator = if due > self.steps {
+            0.
+        } else {
+            (self.accumulator - self.steps as f32 * step).max(0.)
+        };
+
+        self.steps
+    }
+
+    fn has_valid_tick_rate(&self) -> bool {
+        self.tick_rate.is_finite() && self.tick_rate > 0.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_steps_at_any_frame_rate() {
+        let mut fixed_step = FixedTimestep::new(30.);
+
+        // 60 fps runs a step every other frame
+        let steps: Vec<u32> = (0..4).map(|_| fixed_step.advance(1. / 60.)).collect();
+        assert_eq!(steps, vec![0, 1, 0, 1]);
+
+        // 10 fps runs three steps a frame
+        assert_eq!(fixed_step.advance(0.1), 3);
+
+        fixed_step.advance(1. / 60.);
+        assert!((fixed_step.alpha() - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn limits_catch_up_steps() {
+        let mut fixed_step = FixedTimestep::new(60.).with_max_steps_per_frame(4);
+
+        assert_eq!(fixed_step.advance(1.), 4);
+
+        // the backlog is dropped rather than caught up over the next frames
+        assert_eq!(fixed_step.advance(1. / 60.), 1);
+    }
+
+    #[test]
+    fn runs_no_steps_without_a_tick_rate() {
+        for tick_rate in &[0., -30., std::f32::NAN] {
+            let mut fixed_step = FixedTimestep::new(*tick_rate);
+
+            assert_eq!(fixed_step.advance(1.), 0);
+            assert_eq!(fixed_step.steps(), 0);
+            assert_eq!(fixed_step.alpha(), 0.);
+        }
+    }
+}
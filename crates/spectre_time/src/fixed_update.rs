@@ -0,0 +1,177 @@
+use bevy::{
+    ecs::{ArchetypeAccess, System, SystemId, ThreadLocalExecution, TypeAccess},
+    prelude::*,
+};
+use std::borrow::Cow;
+
+use crate::GameTime;
+
+/// The stage which runs the systems added with `add_fixed_system`, after the game time is updated
+pub const FIXED_UPDATE: &str = "fixed_update";
+
+/// Systems added with `add_fixed_system` which the fixed update runner hasn't picked up yet
+#[derive(Default)]
+pub(crate) struct FixedUpdateSystems {
+    systems: Vec<Box<dyn System>>,
+}
+
+/// Adds systems which run once per fixed step of the game time, see `FixedTimestep`
+pub trait AddFixedSystem {
+    /// Adds a system to the fixed update stage. It should use `GameTime::fixed_delta` instead of
+    /// the frame delta, and may run several times in a frame or not at all.
+    fn add_fixed_system(&mut self, system: Box<dyn System>) -> &mut Self;
+}
+
+impl AddFixedSystem for AppBuilder {
+    fn add_fixed_system(&mut self, system: Box<dyn System>) -> &mut Self {
+        if self.resources().get::<FixedUpdateSystems>().is_none() {
+            self.init_resource::<FixedUpdateSystems>();
+        }
+
+        self.resources()
+            .get_mut::<FixedUpdateSystems>()
+            .unwrap()
+            .systems
+            .push(system);
+        self
+    }
+}
+
+/// Runs the fixed update systems once for each step due in the current frame, applying
+/// their commands and counting the tick after each step
+pub(crate) struct FixedUpdateRunner {
+    id: SystemId,
+    systems: Vec<Box<dyn System>>,
+    archetype_access: ArchetypeAccess,
+    resource_access: TypeAccess,
+}
+
+impl FixedUpdateRunner {
+    pub fn new() -> Box<dyn System> {
+        Box::new(FixedUpdateRunner {
+            id: SystemId::new(),
+            systems: Vec::new(),
+            archetype_access: ArchetypeAccess::default(),
+            resource_access: TypeAccess::default(),
+        })
+    }
+
+    /// Moves systems added since the last frame into the runner
+    fn add_queued_systems(&mut self, resources: &mut Resources) {
+        let queued = match resources.get_mut::<FixedUpdateSystems>() {
+            Some(mut queued) => std::mem::take(&mut queued.systems),
+            None => return,
+        };
+
+        for mut system in queued.into_iter() {
+            system.initialize(resources);
+            self.systems.push(system);
+        }
+    }
+}
+
+impl System for FixedUpdateRunner {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("fixed_update_runner")
+    }
+
+    fn id(&self) -> SystemId {
+        self.id
+    }
+
+    // the runner has exclusive access to the world, the fixed systems update their own access as they run
+    fn update_archetype_access(&mut self, _world: &World) {}
+
+    fn archetype_access(&self) -> &ArchetypeAccess {
+        &self.archetype_access
+    }
+
+    fn resource_access(&self) -> &TypeAccess {
+        &self.resource_access
+    }
+
+    fn thread_local_execution(&self) -> ThreadLocalExecution {
+        ThreadLocalExecution::Immediate
+    }
+
+    fn run(&mut self, _world: &World, _resources: &Resources) {}
+
+    fn run_thread_local(&mut self, world: &mut World, resources: &mut Resources) {
+        self.add_queued_systems(resources);
+
+        let steps = match resources.get::<GameTime>() {
+            Some(game_time) => game_time.fixed_step.steps(),
+            None => return,
+        };
+
+        for _ in 0..steps {
+            for system in self.systems.iter_mut() {
+                system.update_archetype_access(world);
+                match system.thread_local_execution() {
+                    ThreadLocalExecution::NextFlush => {
+                        system.run(world, resources);
+                        system.run_thread_local(world, resources);
+                    }
+                    ThreadLocalExecution::Immediate => system.run_thread_local(world, resources),
+                }
+            }
+
+            resources.get_mut::<GameTime>().unwrap().tick += 1;
+        }
+    }
+
+    fn initialize(&mut self, resources: &mut Resources) {
+        self.add_queued_systems(resources);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Spawned;
+
+    /// The tick each step ran on, and the number of entities spawned by earlier steps
+    #[derive(Default)]
+    struct Steps(Vec<(u64, usize)>);
+
+    fn spawn_each_step(
+        mut commands: Commands,
+        game_time: Res<GameTime>,
+        mut steps: ResMut<Steps>,
+        mut query: Query<&Spawned>,
+    ) {
+        let mut spawned = 0;
+        for _ in &mut query.iter() {
+            spawned += 1;
+        }
+
+        steps.0.push((game_time.tick, spawned));
+        commands.spawn((Spawned,));
+    }
+
+    #[test]
+    fn runs_each_due_step() {
+        let mut world = World::new();
+        let mut resources = Resources::default();
+
+        let mut game_time = GameTime::default().with_tick_rate(10.);
+        game_time.fixed_step.advance(0.3);
+        resources.insert(game_time);
+        resources.insert(Steps::default());
+        resources.insert(FixedUpdateSystems {
+            systems: vec![spawn_each_step.system()],
+        });
+
+        let mut runner = FixedUpdateRunner::new();
+        runner.initialize(&mut resources);
+        runner.run_thread_local(&mut world, &mut resources);
+
+        // commands are applied after each step, so later steps see the entities
+        assert_eq!(
+            resources.get::<Steps>().unwrap().0,
+            vec![(0, 0), (1, 1), (2, 2)]
+        );
+        assert_eq!(resources.get::<GameTime>().unwrap().tick, 3);
+    }
+}
@@ -1,10 +1,25 @@
 use bevy::prelude::*;
+use tracing::debug;
+
+mod clock;
+mod fixed_step;
+mod fixed_update;
+
+pub use clock::*;
+pub use fixed_step::*;
+pub use fixed_update::{AddFixedSystem, FIXED_UPDATE};
+
+use fixed_update::FixedUpdateRunner;
 
 /// A resource which stores the current game speed and elapsed game time
 pub struct GameTime {
     pub game_speed: f32,
     pub elapsed_time: f32,
     pub delta: f32,
+
+    /// The number of fixed steps run since the game started
+    pub tick: u64,
+    pub fixed_step: FixedTimestep,
 }
 
 impl Default for GameTime {
@@ -13,15 +28,34 @@ impl Default for GameTime {
             game_speed: 0.0,
             elapsed_time: 0.0,
             delta: 0.0,
+            tick: 0,
+            fixed_step: FixedTimestep::default(),
         }
     }
 }
 
 impl GameTime {
+    /// Sets the number of fixed steps per game second
+    pub fn with_tick_rate(mut self, tick_rate: f32) -> Self {
+        self.fixed_step.tick_rate = tick_rate;
+        self
+    }
+
     /// Returns true if the game is currently paused
     pub fn is_paused(&self) -> bool {
         self.game_speed < 0.01
     }
+
+    /// returns the game time in seconds covered by each fixed step
+    pub fn fixed_delta(&self) -> f32 {
+        self.fixed_step.step_delta()
+    }
+
+    /// returns how far the game time is between the last fixed step and the next, from 0 to 1,
+    /// e.g. to draw sprites between the positions of the last two steps
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.fixed_step.alpha()
+    }
 }
 
 /// Add this on a new entity (with no other components) to request a game speed change
@@ -41,10 +75,20 @@ pub struct GameTimePlugin;
 
 impl Plugin for GameTimePlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.init_resource::<GameTime>()
-            .add_stage_before("update", "game_timer")
+        // the game may already have added its own game time and clocks
+        if app.resources().get::<GameTime>().is_none() {
+            app.init_resource::<GameTime>();
+        }
+        if app.resources().get::<GameClocks>().is_none() {
+            app.init_resource::<GameClocks>();
+        }
+
+        app.add_stage_before("update", "game_timer")
             .add_system_to_stage("game_timer", game_speed_update.system())
-            .add_system_to_stage("game_timer", game_timer.system());
+            .add_system_to_stage("game_timer", game_timer.system())
+            .add_system_to_stage("game_timer", clock_timer.system())
+            .add_stage_after("game_timer", FIXED_UPDATE)
+            .add_system_to_stage(FIXED_UPDATE, FixedUpdateRunner::new());
     }
 }
 
@@ -54,9 +98,10 @@ fn game_speed_update(
     mut query: Query<(Entity, &GameSpeedRequest)>,
 ) {
     for (entity, game_speed) in &mut query.iter() {
-        println!(
-            "Changing game speed from {} to {}",
-            game_time.game_speed, game_speed.new_game_speed
+        debug!(
+            from = game_time.game_speed,
+            to = game_speed.new_game_speed,
+            "Changing game speed"
         );
         game_time.game_speed = game_speed.new_game_speed;
 
@@ -67,9 +112,16 @@ fn game_speed_update(
 fn game_timer(time: Res<Time>, mut game_time: ResMut<GameTime>) {
     game_time.delta = time.delta_seconds * game_time.game_speed;
 
-    if game_time.delta < 0.01 {
+    let delta = game_time.delta;
+    game_time.fixed_step.advance(delta);
+
+    if game_time.is_paused() {
         return;
     }
 
     game_time.elapsed_time += game_time.delta;
 }
+
+fn clock_timer(time: Res<Time>, game_time: Res<GameTime>, mut clocks: ResMut<GameClocks>) {
+    clocks.advance(time.delta_seconds, game_time.delta);
+}
@@ -0,0 +1,29 @@
+use bevy::prelude::*;
+use spectre_loaders::{asset_key, AssetKey, NamedAssets};
+use spectre_log::prelude::*;
+
+// these names match the entries in assets/game.manifest
+pub const WALK_ATLAS: AssetKey<TextureAtlas> = asset_key!("walk_atlas");
+pub const UI_FONT: AssetKey<Font> = asset_key!("ui_font");
+pub const MENU_MUSIC: AssetKey<AudioSource> = asset_key!("menu_music");
+pub const GAME_MUSIC: AssetKey<AudioSource> = asset_key!("game_music");
+
+/// returns the UI font, or None if it hasn't been loaded yet, in which case scenes skip their text
+pub fn ui_font(named_assets: &NamedAssets) -> Option<Handle<Font>> {
+    let font = named_assets.get(UI_FONT);
+    if font.is_none() {
+        warn!("The UI font isn't loaded, scene text is skipped");
+    }
+
+    font
+}
+
+/// returns the walk atlas, or None if it hasn't been loaded yet, in which case units aren't spawned
+pub fn walk_atlas(named_assets: &NamedAssets) -> Option<Handle<TextureAtlas>> {
+    let atlas = named_assets.get(WALK_ATLAS);
+    if atlas.is_none() {
+        warn!("The walk atlas isn't loaded, units can't be spawned");
+    }
+
+    atlas
+}
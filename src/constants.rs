@@ -1 +0,0 @@
-pub const ANIMATED_SPRITESHEED_ID: u128 = 324890576394765893475;
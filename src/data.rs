@@ -1,19 +1,172 @@
 use bevy::prelude::*;
-use serde::Deserialize;
-use spectre_loaders::data_loaders::DataFileLoader;
+use serde::{Deserialize, Serialize};
+use spectre_loaders::{
+    data_loaders::{DataFileErrors, DataFileLoader, DataFileTypes, DataFormat},
+    AddPackedAssetLoader, AssetCollections, AssetGroupLoaded, AssetRef, DataAsset,
+    DataDependencies, DataFileRegistry, DataReloadPlugin, DataSource, FromDataAsset, Validator,
+};
 
-#[derive(Deserialize)]
+/// characters are written in RON, exported from tools as JSON and shipped as binary
+const CHARACTER_EXTENSION: &str = "chd";
+const CHARACTER_JSON_EXTENSION: &str = "chdj";
+const CHARACTER_BINARY_EXTENSION: &str = "chdb";
+const ABILITY_EXTENSION: &str = "abl";
+
+/// The collection the manifest loads character files into
+const CHARACTER_COLLECTION: &str = "characters";
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Character {
-    pub test: i32,
+    pub name: String,
+    pub health: f32,
+    pub mana: f32,
+    pub abilities: Vec<AssetRef<Ability>>,
+}
+
+impl DataAsset for Character {
+    fn validate(&self, validator: &mut Validator) {
+        validator.check(!self.name.is_empty(), "name must not be empty");
+        validator.check_range("health", self.health, 1., 10000.);
+        validator.check_range("mana", self.mana, 0., 10000.);
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Ability {
+    pub name: String,
+    pub mana_cost: f32,
+
+    /// The time in seconds before the ability can be used again
+    pub cooldown: f32,
+    pub icon: AssetRef<Texture>,
+}
+
+impl DataAsset for Ability {
+    fn validate(&self, validator: &mut Validator) {
+        validator.check(!self.name.is_empty(), "name must not be empty");
+        validator.check_range("mana_cost", self.mana_cost, 0., 10000.);
+        validator.check_range("cooldown", self.cooldown, 0., 600.);
+    }
+}
+
+/// Characters spawned with a `DataSource<Character>` are updated when their file is reloaded
+impl FromDataAsset<Character> for Character {
+    fn apply_data(&mut self, asset: &Character) {
+        *self = asset.clone();
+    }
 }
 
 pub struct DataFileLoaderPlugin;
 
 impl Plugin for DataFileLoaderPlugin {
     fn build(&self, app: &mut AppBuilder) {
+        // parse errors, references and types are handled by the ResourceLoaderPlugin, which must be added first
+        let errors = app
+            .resources()
+            .get::<DataFileErrors>()
+            .expect("The ResourceLoaderPlugin must be added before the DataFileLoaderPlugin")
+            .clone();
+        let dependencies = app
+            .resources()
+            .get::<DataDependencies>()
+            .expect("The ResourceLoaderPlugin must be added before the DataFileLoaderPlugin")
+            .clone();
+        let types = app
+            .resources()
+            .get::<DataFileTypes>()
+            .expect("The ResourceLoaderPlugin must be added before the DataFileLoaderPlugin")
+            .clone();
+
+        let character_loader = DataFileLoader::from_extensions(vec![CHARACTER_EXTENSION])
+            .with_format(CHARACTER_JSON_EXTENSION, DataFormat::Json)
+            .with_format(CHARACTER_BINARY_EXTENSION, DataFormat::Bincode)
+            .with_errors(&errors)
+            .with_dependencies(&dependencies)
+            .with_types(&types);
+        let ability_loader = DataFileLoader::from_extensions(vec![ABILITY_EXTENSION])
+            .with_errors(&errors)
+            .with_dependencies(&dependencies)
+            .with_types(&types);
+
         app.add_asset::<Character>()
-            .add_asset_loader_from_instance::<Character, DataFileLoader>(
-                DataFileLoader::from_extensions(vec!["chd"]),
-            );
+            .add_asset_loader_from_instance::<Character, DataFileLoader>(character_loader.clone())
+            .add_packed_asset_loader::<Character, _>(character_loader)
+            .add_asset::<Ability>()
+            .add_asset_loader_from_instance::<Ability, DataFileLoader>(ability_loader.clone())
+            .add_packed_asset_loader::<Ability, _>(ability_loader)
+            .add_plugin(DataReloadPlugin::<Character, Character>::default())
+            .add_system(spawn_characters.system());
+    }
+}
+
+/// spawns an entity for each loaded character file, which is updated when the file is reloaded
+fn spawn_characters(
+    mut commands: Commands,
+    mut spawned: Local<Vec<Handle<Character>>>,
+    mut event_reader: Local<EventReader<AssetGroupLoaded>>,
+    events: Res<Events<AssetGroupLoaded>>,
+    collections: Res<AssetCollections>,
+    characters: Res<Assets<Character>>,
+) {
+    // characters can be in any group, so check each time a group loads
+    if event_reader.iter(&events).count() == 0 {
+        return;
+    }
+
+    for (_, handle) in collections.iter::<Character>(CHARACTER_COLLECTION) {
+        if spawned.contains(&handle) {
+            continue;
+        }
+
+        if let Some(character) = characters.get(&handle) {
+            commands.spawn((character.clone(), DataSource::new(handle)));
+            spawned.push(handle);
+        }
+    }
+}
+
+/// returns a registry of every data file type used by the game
+pub fn data_file_registry() -> DataFileRegistry {
+    DataFileRegistry::default()
+        .register::<Character>(&[CHARACTER_EXTENSION])
+        .register_format::<Character>(CHARACTER_JSON_EXTENSION, DataFormat::Json)
+        .register_binary::<Character>(CHARACTER_EXTENSION, CHARACTER_BINARY_EXTENSION)
+        .register::<Ability>(&[ABILITY_EXTENSION])
+}
+
+/// Validates every data file in the assets folder without starting the game,
+/// printing any errors. Returns true if every file is valid.
+pub fn validate_data_files() -> bool {
+    let (checked, errors) = data_file_registry().validate_directory("assets");
+
+    for error in errors.iter() {
+        println!("{}", error);
     }
+
+    println!(
+        "Checked {} data files, {} with errors",
+        checked,
+        errors.len()
+    );
+
+    errors.is_empty()
+}
+
+/// Converts the RON data files in the assets folder to binary files in the output
+/// folder, for shipping builds. Returns true if every file was converted.
+pub fn pack_data_files(output: &str) -> bool {
+    let (converted, errors) = data_file_registry().convert_directory("assets", output);
+
+    for error in errors.iter() {
+        println!("{}", error);
+    }
+
+    println!(
+        "Converted {} data files to {}, {} with errors",
+        converted,
+        output,
+        errors.len()
+    );
+
+    errors.is_empty()
 }
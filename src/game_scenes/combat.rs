@@ -0,0 +1,59 @@
+use bevy::prelude::*;
+use spectre_loaders::NamedAssets;
+use spectre_state::*;
+
+use crate::assets::*;
+
+use super::GameModes;
+
+pub struct CombatScene;
+
+impl Scene for CombatScene {
+    type Scenes = GameModes;
+
+    fn scene(&self) -> GameModes {
+        GameModes::Combat
+    }
+
+    fn on_enter(&self) -> Vec<Box<dyn System>> {
+        vec![setup_combat_scene.system()]
+    }
+}
+
+/// switches between exploring and combat when tab is pressed, runs in the game scene
+pub fn toggle_combat(input: Res<Input<KeyCode>>, mut game_modes: ResMut<GameState<GameModes>>) {
+    if !input.just_pressed(KeyCode::Tab) {
+        return;
+    }
+
+    match game_modes.current {
+        Some(GameModes::Exploring) => game_modes.set_transition(GameModes::Combat),
+        Some(GameModes::Combat) => game_modes.set_transition(GameModes::Exploring),
+        None => {}
+    }
+}
+
+pub fn setup_combat_scene(mut commands: Commands, named_assets: Res<NamedAssets>) {
+    let font_handle = match ui_font(&named_assets) {
+        Some(font) => font,
+        None => return,
+    };
+
+    commands
+        .spawn(TextComponents {
+            style: Style {
+                align_self: AlignSelf::FlexStart,
+                ..Default::default()
+            },
+            text: Text {
+                value: "COMBAT - press [TAB] to stop fighting".to_string(),
+                font: font_handle,
+                style: TextStyle {
+                    font_size: 20.0,
+                    color: Color::rgb(0.9, 0.2, 0.2),
+                },
+            },
+            ..Default::default()
+        })
+        .with(SceneOwned(GameModes::Combat));
+}
@@ -1,24 +1,49 @@
 use bevy::prelude::*;
-use spectre_animations::prelude::spawn_animated_spritesheet;
+use spectre_animations::prelude::{spawn_animated_spritesheet, AnimationClock};
+use spectre_loaders::NamedAssets;
 use spectre_state::*;
 
-use super::MyGameScenes;
-use crate::constants::ANIMATED_SPRITESHEED_ID;
+use crate::assets::*;
 
-pub struct GameSceneEntity;
+use super::{pause_game, toggle_combat, MyGameScenes};
+
+pub struct GameScene;
+
+impl Scene for GameScene {
+    type Scenes = MyGameScenes;
+
+    fn scene(&self) -> MyGameScenes {
+        // every level is part of the game scene
+        MyGameScenes::Game { level: 1 }
+    }
+
+    fn on_enter(&self) -> Vec<Box<dyn System>> {
+        vec![setup_game_scene.system()]
+    }
+
+    fn on_update(&self) -> Vec<Box<dyn System>> {
+        vec![
+            run_game_scene.system(),
+            pause_game.system(),
+            toggle_combat.system(),
+            next_level.system(),
+        ]
+    }
+}
 
 pub fn setup_game_scene(
     mut commands: Commands,
     game_state: Res<GameState<MyGameScenes>>,
-    asset_server: Res<AssetServer>,
+    named_assets: Res<NamedAssets>,
 ) {
-    if !game_state.is_in_scene(&MyGameScenes::Game)
-        || !game_state.is_in_status(&GameStatus::Entering)
-    {
-        return;
-    }
-
-    let font_handle = asset_server.load("assets/fonts/teletactile.ttf").unwrap();
+    let font_handle = match ui_font(&named_assets) {
+        Some(font) => font,
+        None => return,
+    };
+    let level = match game_state.current_scene() {
+        Some(MyGameScenes::Game { level }) => *level,
+        _ => return,
+    };
 
     commands
         .spawn(TextComponents {
@@ -27,7 +52,10 @@ pub fn setup_game_scene(
                 ..Default::default()
             },
             text: Text {
-                value: "Press [SPACE] to spawn a unit".to_string(),
+                value: format!(
+                    "Level {} - press [SPACE] to spawn a unit, [TAB] to fight, [N] for the next level",
+                    level
+                ),
                 font: font_handle,
                 style: TextStyle {
                     font_size: 20.0,
@@ -36,52 +64,48 @@ pub fn setup_game_scene(
             },
             ..Default::default()
         })
-        .with(GameSceneEntity);
+        .with(SceneOwned(MyGameScenes::Game { level }));
+}
+
+/// moves to the next level when N is pressed
+pub fn next_level(input: Res<Input<KeyCode>>, mut game_state: ResMut<GameState<MyGameScenes>>) {
+    if !input.just_pressed(KeyCode::N) {
+        return;
+    }
+
+    if let Some(MyGameScenes::Game { level }) = game_state.current_scene() {
+        let next = MyGameScenes::Game { level: level + 1 };
+        game_state.set_transition(next);
+    }
 }
 
 // demonstrates spawning a player using the spawn_animated_spritesheet helper
 pub fn run_game_scene(
-    commands: Commands,
+    mut commands: Commands,
     input: Res<Input<KeyCode>>,
     game_state: Res<GameState<MyGameScenes>>,
-    textures: ResMut<Assets<Texture>>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    named_assets: Res<NamedAssets>,
 ) {
-    if !game_state.is_in_scene(&MyGameScenes::Game) {
-        return;
-    }
-
     if !input.just_pressed(KeyCode::Space) {
         return;
     }
 
-    let handle: Handle<Texture> = Handle::from_u128(ANIMATED_SPRITESHEED_ID);
-    let texture = textures.get(&handle).unwrap();
-    let texture_atlas = TextureAtlas::from_grid(handle, texture.size, 9, 4);
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
+    let texture_atlas_handle = match walk_atlas(&named_assets) {
+        Some(atlas) => atlas,
+        None => return,
+    };
+    let scene = match game_state.current_scene() {
+        Some(scene) => *scene,
+        None => return,
+    };
 
     spawn_animated_spritesheet(
-        commands,
+        &mut commands,
         texture_atlas_handle,
         0.05,
         vec![(0, 8), (9, 17), (18, 26), (27, 35)],
         Vec3::new(0., 0., 0.),
+        AnimationClock::GameTime,
     )
-}
-
-pub fn teardown_game_scene(
-    mut commands: Commands,
-    game_state: Res<GameState<MyGameScenes>>,
-    mut loading_scene_items: Query<(Entity, &GameSceneEntity)>,
-) {
-    if !game_state.is_in_scene(&MyGameScenes::Game)
-        || !game_state.is_in_status(&GameStatus::Exiting)
-    {
-        return;
-    }
-
-    println!("Tearing down loading screen");
-    for (entity, _) in &mut loading_scene_items.iter() {
-        commands.despawn(entity);
-    }
+    .with(SceneOwned(scene));
 }
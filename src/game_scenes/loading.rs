@@ -1,44 +1,81 @@
 use bevy::prelude::*;
-use spectre_loaders::LoadingStatus;
+use spectre_loaders::{LoadingStatus, NamedAssets};
+use spectre_log::prelude::*;
 use spectre_state::*;
 
+use crate::assets::*;
+
 use super::MyGameScenes;
 
 pub struct LoadingSceneEntity;
 
+pub struct LoadingScene;
+
+impl Scene for LoadingScene {
+    type Scenes = MyGameScenes;
+
+    fn scene(&self) -> MyGameScenes {
+        MyGameScenes::Loading
+    }
+
+    fn on_enter(&self) -> Vec<Box<dyn System>> {
+        vec![setup_loading_scene.system()]
+    }
+
+    fn on_update(&self) -> Vec<Box<dyn System>> {
+        vec![run_loading_scene.system()]
+    }
+}
+
 pub fn run_loading_scene(
-    game_state: Res<GameState<MyGameScenes>>,
     loading_state: Res<LoadingStatus>,
+    mut shown_phase: Local<Option<String>>,
     mut loading_text: Query<With<LoadingSceneEntity, &mut Text>>,
 ) {
-    if !game_state.is_in_scene(&MyGameScenes::Loading)
-        || !game_state.is_in_status(&GameStatus::Running)
-    {
+    trace!("Running loading screen");
+    if loading_state.has_errors() {
+        for mut text in &mut loading_text.iter() {
+            text.value = format!(
+                "Failed to load {} assets",
+                loading_state.items_failed - loading_state.items_using_fallback
+            );
+        }
+
         return;
     }
 
-    println!("Running loading screen");
+    // the current phase is cleared once its group loads, so the last phase is kept to show it at 100%
+    if let Some(current) = &loading_state.current_phase {
+        *shown_phase = Some(current.clone());
+    }
+
+    let phase = match shown_phase
+        .as_ref()
+        .and_then(|group| loading_state.group(group))
+    {
+        Some(phase) => phase,
+        None => return,
+    };
+
     for mut text in &mut loading_text.iter() {
-        text.value = format!(
-            "Loading {} of {}",
-            loading_state.items_loaded, loading_state.items_to_load
-        );
+        text.value = format!("Loading {} ({}%)", phase.label, phase.percentage());
     }
 }
 
 pub fn setup_loading_scene(
     mut commands: Commands,
-    game_state: Res<GameState<MyGameScenes>>,
-    asset_server: Res<AssetServer>,
+    named_assets: Res<NamedAssets>,
+    mut console: ResMut<LogConsole>,
 ) {
-    if !game_state.is_in_scene(&MyGameScenes::Loading)
-        || !game_state.is_in_status(&GameStatus::Entering)
-    {
-        return;
-    }
+    debug!("Setting up loading screen");
+    let font_handle = match ui_font(&named_assets) {
+        Some(font) => font,
+        None => return,
+    };
+
+    // the log console is shown once the UI font is loaded
+    console.font = Some(font_handle);
 
-    println!("Setting up loading screen");
-    let font_handle = asset_server.load("assets/fonts/teletactile.ttf").unwrap();
     commands
         // texture
         .spawn(TextComponents {
@@ -56,22 +93,6 @@ pub fn setup_loading_scene(
             },
             ..Default::default()
         })
-        .with(LoadingSceneEntity);
-}
-
-pub fn teardown_loading_scene(
-    mut commands: Commands,
-    game_state: Res<GameState<MyGameScenes>>,
-    mut loading_scene_items: Query<(Entity, &LoadingSceneEntity)>,
-) {
-    if !game_state.is_in_scene(&MyGameScenes::Loading)
-        || !game_state.is_in_status(&GameStatus::Exiting)
-    {
-        return;
-    }
-
-    println!("Tearing down loading screen");
-    for (entity, _) in &mut loading_scene_items.iter() {
-        commands.despawn(entity);
-    }
+        .with(LoadingSceneEntity)
+        .with(SceneOwned(MyGameScenes::Loading));
 }
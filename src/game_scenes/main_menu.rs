@@ -1,23 +1,39 @@
 use bevy::prelude::*;
+use spectre_loaders::NamedAssets;
 use spectre_state::*;
 
+use crate::assets::*;
+
 use super::{ButtonMaterials, MyGameScenes};
 
-pub struct MainMenuSceneEntity;
 pub struct MenuButtonText;
 
+pub struct MenuScene;
+
+impl Scene for MenuScene {
+    type Scenes = MyGameScenes;
+
+    fn scene(&self) -> MyGameScenes {
+        MyGameScenes::Menu
+    }
+
+    fn on_enter(&self) -> Vec<Box<dyn System>> {
+        vec![setup_menu_scene.system()]
+    }
+
+    fn on_update(&self) -> Vec<Box<dyn System>> {
+        vec![run_menu_scene.system()]
+    }
+}
+
 pub fn run_menu_scene(
     mut game_state: ResMut<GameState<MyGameScenes>>,
     mut interaction_query: Query<(&Button, Mutated<Interaction>)>,
 ) {
-    if !game_state.is_in_scene(&MyGameScenes::Menu) {
-        return;
-    }
-
     for (_button, interaction) in &mut interaction_query.iter() {
         match *interaction {
             Interaction::Clicked => {
-                game_state.set_transition(MyGameScenes::Game);
+                game_state.set_transition(MyGameScenes::Game { level: 1 });
             }
             _ => {}
         }
@@ -26,18 +42,14 @@ pub fn run_menu_scene(
 
 pub fn setup_menu_scene(
     mut commands: Commands,
-    game_state: Res<GameState<MyGameScenes>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     button_materials: Res<ButtonMaterials>,
-    asset_server: Res<AssetServer>,
+    named_assets: Res<NamedAssets>,
 ) {
-    if !game_state.is_in_scene(&MyGameScenes::Menu)
-        || !game_state.is_in_status(&GameStatus::Entering)
-    {
-        return;
-    }
-
-    let font_handle = asset_server.load("assets/fonts/teletactile.ttf").unwrap();
+    let font_handle = match ui_font(&named_assets) {
+        Some(font) => font,
+        None => return,
+    };
     commands
         .spawn(NodeComponents {
             style: Style {
@@ -97,22 +109,5 @@ pub fn setup_menu_scene(
                 })
                 .with(MenuButtonText);
         })
-        .with(MainMenuSceneEntity);
-}
-
-pub fn teardown_menu_scene(
-    mut commands: Commands,
-    game_state: Res<GameState<MyGameScenes>>,
-    mut menu_scene_items: Query<(Entity, &MainMenuSceneEntity)>,
-) {
-    if !game_state.is_in_scene(&MyGameScenes::Menu)
-        || !game_state.is_in_status(&GameStatus::Exiting)
-    {
-        return;
-    }
-
-    println!("Tearing down loading screen");
-    for (entity, _) in &mut menu_scene_items.iter() {
-        commands.despawn_recursive(entity);
-    }
+        .with(SceneOwned(MyGameScenes::Menu));
 }
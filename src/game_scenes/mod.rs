@@ -1,21 +1,42 @@
 use bevy::prelude::*;
-use spectre_loaders::LoadingStatus;
+use spectre_audio::PlayMusic;
+use spectre_loaders::{LoadingStatus, NamedAssets};
 use spectre_state::*;
 
+use crate::assets::*;
+
+mod combat;
 mod game;
 mod loading;
 mod main_menu;
+mod pause;
 
+use combat::*;
 use game::*;
 use loading::*;
 use main_menu::*;
+use pause::*;
 
 #[derive(Clone, Copy, Debug)]
 #[allow(dead_code)]
 pub enum MyGameScenes {
     Loading,
     Menu,
-    Game,
+
+    /// moving to another level exits and re-enters the game scene
+    Game {
+        level: u32,
+    },
+
+    /// pushed over the game scene, which is paused until this scene is popped
+    Paused,
+}
+
+/// Sub states of the game scene, which only run while the game scene is running
+#[derive(Clone, Copy, Debug)]
+pub enum GameModes {
+    Exploring,
+    Combat,
 }
 
 pub struct ButtonMaterials {
@@ -40,37 +61,41 @@ pub struct GameStatePlugin;
 impl Plugin for GameStatePlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<ButtonMaterials>()
-            .add_resource(GameState::<MyGameScenes> {
-                status: GameStatus::Idle,
-                current: None,
-                next: None,
-            })
+            .init_resource::<SpeedBeforePause>()
+            .add_resource(
+                GameState::<MyGameScenes>::default()
+                    .with_transition(SceneTransition::fade(Color::BLACK, 0.4)),
+            )
             .add_system(game_state_transitions.system())
-            // loading scene - TODO as plugin?
-            .add_system(setup_loading_scene.system())
-            .add_system(run_loading_scene.system())
-            .add_system(teardown_loading_scene.system())
-            // menu scene - TODO as plugin?
-            .add_system(setup_menu_scene.system())
-            .add_system(run_menu_scene.system())
-            .add_system(teardown_menu_scene.system())
-            // game scene - TODO as plugin?
-            .add_system(setup_game_scene.system())
-            .add_system(run_game_scene.system())
-            .add_system(teardown_game_scene.system());
+            .add_system(scene_music.system())
+            .add_plugin(ScenePlugin::new(LoadingScene))
+            .add_plugin(ScenePlugin::new(MenuScene))
+            .add_plugin(ScenePlugin::new(GameScene))
+            .add_plugin(ScenePlugin::new(PauseScene))
+            .add_plugin(SubStatePlugin::new(
+                MyGameScenes::Game { level: 1 },
+                GameModes::Exploring,
+            ))
+            .add_plugin(ScenePlugin::new(CombatScene))
+            .add_plugin(SceneTransitionPlugin::<MyGameScenes>::default());
     }
 }
 
 fn game_state_transitions(
+    time: Res<Time>,
     loading: Res<LoadingStatus>,
     mut game_state: ResMut<GameState<MyGameScenes>>,
 ) {
-    game_state.update();
+    // keep the screen covered until any assets requested for the next scene are loaded
+    game_state.set_hold("loading", loading.is_loading());
+
+    // real time is used so transitions still play while game time is paused
+    game_state.advance(time.delta_seconds);
     match game_state.status {
         GameStatus::Idle => game_state.set_transition(MyGameScenes::Loading),
         GameStatus::Running => match game_state.current {
             Some(MyGameScenes::Loading) => {
-                if loading.initial_load_done {
+                if loading.is_group_loaded("core") && !loading.has_errors() {
                     game_state.set_transition(MyGameScenes::Menu);
                 }
             }
@@ -79,3 +104,30 @@ fn game_state_transitions(
         _ => {}
     };
 }
+
+/// crossfades to the music for a scene as it is entered, if the music has loaded
+fn scene_music(
+    mut event_reader: Local<EventReader<SceneTransitionEvent<MyGameScenes>>>,
+    events: Res<Events<SceneTransitionEvent<MyGameScenes>>>,
+    named_assets: Res<NamedAssets>,
+    mut music: ResMut<Events<PlayMusic>>,
+) {
+    for event in event_reader.iter(&events) {
+        if event.phase != TransitionPhase::Entering {
+            continue;
+        }
+
+        let track = match event.to {
+            Some(MyGameScenes::Menu) => MENU_MUSIC,
+            Some(MyGameScenes::Game { .. }) => GAME_MUSIC,
+            _ => continue,
+        };
+
+        if let Some(source) = named_assets.get(track) {
+            music.send(PlayMusic {
+                source,
+                crossfade: 1.5,
+            });
+        }
+    }
+}
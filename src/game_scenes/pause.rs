@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+use spectre_loaders::NamedAssets;
+use spectre_state::*;
+use spectre_time::{GameSpeedRequest, GameTime};
+
+use crate::assets::*;
+
+use super::MyGameScenes;
+
+pub struct PauseScene;
+
+impl Scene for PauseScene {
+    type Scenes = MyGameScenes;
+
+    fn scene(&self) -> MyGameScenes {
+        MyGameScenes::Paused
+    }
+
+    fn on_enter(&self) -> Vec<Box<dyn System>> {
+        vec![setup_pause_scene.system()]
+    }
+
+    fn on_update(&self) -> Vec<Box<dyn System>> {
+        vec![run_pause_scene.system()]
+    }
+
+    fn on_exit(&self) -> Vec<Box<dyn System>> {
+        vec![resume_game_time.system()]
+    }
+}
+
+/// The game speed when the game was paused, which is restored when it resumes
+pub struct SpeedBeforePause(pub f32);
+
+impl Default for SpeedBeforePause {
+    fn default() -> Self {
+        SpeedBeforePause(1.)
+    }
+}
+
+/// pushes the pause scene over the game when escape is pressed, runs in the game scene.
+/// Pausing and resuming are instant rather than fading the screen.
+pub fn pause_game(input: Res<Input<KeyCode>>, mut game_state: ResMut<GameState<MyGameScenes>>) {
+    if input.just_pressed(KeyCode::Escape) {
+        game_state.change_scene(
+            SceneChange::Push(MyGameScenes::Paused),
+            SceneTransition::default(),
+        );
+    }
+}
+
+pub fn setup_pause_scene(
+    mut commands: Commands,
+    named_assets: Res<NamedAssets>,
+    game_time: Res<GameTime>,
+    mut speed_before_pause: ResMut<SpeedBeforePause>,
+) {
+    // stop game time while the game is paused
+    speed_before_pause.0 = game_time.game_speed;
+    commands.spawn((GameSpeedRequest::new(0.),));
+
+    let font_handle = match ui_font(&named_assets) {
+        Some(font) => font,
+        None => return,
+    };
+
+    commands
+        .spawn(TextComponents {
+            style: Style {
+                align_self: AlignSelf::Center,
+                ..Default::default()
+            },
+            text: Text {
+                value: "PAUSED - press [ESC] to resume".to_string(),
+                font: font_handle,
+                style: TextStyle {
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            },
+            ..Default::default()
+        })
+        .with(SceneOwned(MyGameScenes::Paused));
+}
+
+pub fn run_pause_scene(
+    input: Res<Input<KeyCode>>,
+    mut game_state: ResMut<GameState<MyGameScenes>>,
+) {
+    if input.just_pressed(KeyCode::Escape) {
+        game_state.change_scene(SceneChange::Pop, SceneTransition::default());
+    }
+}
+
+pub fn resume_game_time(mut commands: Commands, speed_before_pause: Res<SpeedBeforePause>) {
+    commands.spawn((GameSpeedRequest::new(speed_before_pause.0),));
+}
@@ -1,19 +1,43 @@
 use bevy::{prelude::*, render::pass::ClearColor, window::WindowMode};
-use spectre_animations::prelude::AnimationPlugin;
+use spectre_animations::prelude::{AnimationPlugin, TweenPlugin};
+use spectre_audio::prelude::AudioMixerPlugin;
 use spectre_combat::prelude::AllegiancePlugin;
 use spectre_core::prelude::{BuffableStatistic, CharacterStats, Health, Mana, Movement, Stats};
-use spectre_loaders::{LoadAssets, ResourceLoaderPlugin};
+use spectre_loaders::{AssetManifest, AssetPacks, HotReloadPlugin, ResourceLoaderPlugin};
+use spectre_log::{init_logging, LogConsolePlugin, DEFAULT_LOG_FILTER};
 use spectre_time::{GameSpeedRequest, GameTimePlugin};
+use std::path::Path;
 
-mod constants;
+mod assets;
 mod data;
 mod game_scenes;
+mod tools;
 
-use constants::ANIMATED_SPRITESHEED_ID;
 use data::DataFileLoaderPlugin;
 use game_scenes::*;
+use tools::run_tool;
 
 fn main() {
+    // e.g. `cargo run -- --validate-data`, see `tools::run_tool`
+    if let Some(succeeded) = run_tool() {
+        std::process::exit(if succeeded { 0 } else { 1 });
+    }
+
+    // filter with e.g. `RUST_LOG=info,spectre_loaders=debug`, see `spectre_log::log_filter`
+    let logs = init_logging(DEFAULT_LOG_FILTER);
+
+    // shipping builds load assets from a pack, with mod packs layered on top
+    let mut packs = AssetPacks::default();
+    if Path::new("assets.pak").exists() {
+        packs = packs.with_pack("assets.pak");
+    }
+    let packs = packs.with_mods("mods");
+
+    let manifest = packs
+        .read("assets/game.manifest")
+        .and_then(|bytes| AssetManifest::from_bytes(&bytes))
+        .expect("Unable to read asset manifest");
+
     App::build()
         .add_resource(WindowDescriptor {
             title: "Spectre".to_string(),
@@ -25,18 +49,24 @@ fn main() {
             ..Default::default()
         })
         .add_resource(ClearColor(Color::rgb(0.005, 0.005, 0.005)))
+        .add_resource(manifest)
+        .add_resource(packs)
         .add_default_plugins()
         .add_startup_system(setup.system())
         .add_plugin(GameTimePlugin)
+        .add_plugin(HotReloadPlugin)
         .add_plugin(ResourceLoaderPlugin)
         .add_plugin(DataFileLoaderPlugin)
         .add_plugin(AllegiancePlugin)
         .add_plugin(AnimationPlugin)
+        .add_plugin(TweenPlugin)
+        .add_plugin(AudioMixerPlugin::default())
         .add_plugin(GameStatePlugin)
+        .add_plugin(LogConsolePlugin::new(logs))
         .run();
 }
 
-fn setup(mut commands: Commands) {
+fn setup(mut commands: Commands, manifest: Res<AssetManifest>) {
     // spawn the camera
     commands
         .spawn(Camera2dComponents::default())
@@ -51,18 +81,11 @@ fn setup(mut commands: Commands) {
             },
             health: Health::new(100.),
             mana: Mana::new(200.),
-            movement: Movement {
-                movement_speed: BuffableStatistic::new(50.),
-            },
+            movement: Movement::new(50.),
         })
         // this loaders approach requires at least one tick of the game loop before
         // assets handles are available, therefore can't directly spawn player sprite here
-        .spawn((LoadAssets {
-            assets: vec![("assets/walk_sprite_sheet.png", ANIMATED_SPRITESHEED_ID)]
-                .into_iter()
-                .map(|a| a.into())
-                .collect(),
-        },))
+        .spawn((manifest.load_group("core").unwrap(),))
         // start the game clock running
         .spawn((GameSpeedRequest::new(1.0),));
 }
@@ -0,0 +1,55 @@
+use spectre_loaders::PackWriter;
+
+use crate::data::{pack_data_files, validate_data_files};
+
+/// Runs a command line tool if one was requested, without opening a window.
+/// Returns None if no tool was requested, otherwise true if the tool succeeded.
+///
+/// - `--validate-data` checks every data file in the assets folder
+/// - `--pack-data [output]` converts the RON data files to binary for shipping
+/// - `--pack-assets [output] [--compress]` packs the assets folder into a single file
+pub fn run_tool() -> Option<bool> {
+    let args: Vec<String> = std::env::args().collect();
+    let argument = |index: usize| {
+        args.get(index + 1)
+            .filter(|arg| !arg.starts_with("--"))
+            .map(|arg| arg.as_str())
+    };
+
+    if args.iter().any(|arg| arg == "--validate-data") {
+        return Some(validate_data_files());
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--pack-data") {
+        return Some(pack_data_files(argument(index).unwrap_or("build/assets")));
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--pack-assets") {
+        let compress = args.iter().any(|arg| arg == "--compress");
+        return Some(pack_assets(
+            argument(index).unwrap_or("assets.pak"),
+            compress,
+        ));
+    }
+
+    None
+}
+
+fn pack_assets(output: &str, compress: bool) -> bool {
+    let mut writer = PackWriter::new(compress);
+
+    let result = writer
+        .add_directory("assets")
+        .and_then(|count| writer.write(output).map(|_| count));
+
+    match result {
+        Ok(count) => {
+            println!("Packed {} files into {}", count, output);
+            true
+        }
+        Err(err) => {
+            println!("Unable to pack assets: {}", err);
+            false
+        }
+    }
+}